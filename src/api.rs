@@ -1,25 +1,48 @@
-use crate::player::{Player, Position};
+use crate::money::Money;
+use crate::player::{Player, Position, SeasonStats, Status};
+use crate::source::{Fixture, Gameweek, TeamInfo};
 use crate::squad::Squad;
 use crate::team::Team;
-use reqwest::cookie::Cookie;
-use reqwest::header::HeaderMap;
-use reqwest::header::HeaderName;
-use reqwest::header::HeaderValue;
-
-use serde::ser::{SerializeMap, SerializeTuple};
-use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::borrow::Cow;
+use crate::transfer::Transfer;
+
+use reqwest::cookie::{CookieStore, Jar};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt;
-use std::io::{Error, ErrorKind};
+use std::fs;
+use std::io::{Error, Write};
+use std::path::Path;
+use std::sync::Arc;
 
 const FANTASY_API_URL: &str = "https://fantasy.premierleague.com/api/bootstrap-static/";
 const LOG_IN_URL: &str = "https://users.premierleague.com/accounts/login/";
 const TRANSFER_URL: &str = "https://fantasy.premierleague.com/api/transfers/";
+const FANTASY_URL: &str = "https://fantasy.premierleague.com/";
+const USERS_URL: &str = "https://users.premierleague.com/";
+const ME_URL: &str = "https://fantasy.premierleague.com/api/me/";
+const PROFILE_COOKIE: &str = "pl_profile";
+const FIXTURES_URL: &str = "https://fantasy.premierleague.com/api/fixtures/?future=1";
 
+// The bootstrap-static response, players plus the teams and gameweeks of the season
 #[derive(Deserialize)]
 pub struct PlayerResponse {
     pub elements: Vec<APIPlayer>,
+    #[serde(default)]
+    pub teams: Vec<TeamInfo>,
+    #[serde(default)]
+    pub events: Vec<Gameweek>,
+}
+
+impl PlayerResponse {
+    pub fn from_json(json: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(serde_json::from_str(json)?)
+    }
+    // Highest metric first
+    pub fn sorted_players(&self) -> Vec<Player> {
+        let mut result: Vec<Player> = self.elements.iter().map(|p| p.to_player()).collect();
+        result.sort_by(|b, a| a.metric().partial_cmp(&b.metric()).unwrap());
+        result
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -28,11 +51,28 @@ pub struct APIPlayer {
     form: String,
     element_type: u8,
     web_name: String,
-    now_cost: f32,
+    now_cost: i32,
     team: u8,
     id: u16,
     total_points: i32,
     ep_next: String,
+    #[serde(default)]
+    status: Status,
+    #[serde(default)]
+    news: String,
+    news_added: Option<String>,
+    #[serde(default)]
+    selected_by_percent: String,
+    #[serde(default)]
+    minutes: u32,
+    #[serde(default)]
+    starts: u32,
+    #[serde(default)]
+    goals_scored: u32,
+    #[serde(default)]
+    assists: u32,
+    #[serde(default)]
+    bonus: u32,
 }
 
 impl APIPlayer {
@@ -46,28 +86,47 @@ impl APIPlayer {
             _ => None,
         };
 
-        Player::new(
+        let mut player = Player::new(
             self.form.parse::<f32>().unwrap(),
             self.chance_of_playing_next_round.unwrap_or(100.0) / 100.0,
-            self.now_cost / 10.0,
+            Money::from_tenths(self.now_cost),
             self.web_name.clone(),
             position.unwrap(),
             self.id,
             team,
             self.total_points,
             self.ep_next.parse::<f32>().unwrap(),
-        )
+        );
+        player.status = self.status;
+        player.news = self.news.clone();
+        player.news_added = self.news_added.clone();
+        player.selected_by_percent = self.selected_by_percent.parse().unwrap_or(0.0);
+        player.stats = SeasonStats {
+            minutes: self.minutes,
+            starts: self.starts,
+            goals_scored: self.goals_scored,
+            assists: self.assists,
+            bonus: self.bonus,
+        };
+        player
     }
 }
 
 #[derive(Deserialize, Debug)]
 struct APIEntryHistory {
-    bank: f32,
+    bank: i32,
+    #[serde(default)]
+    points: i32,
+    #[serde(default)]
+    total_points: i32,
 }
 
 #[derive(Deserialize, Debug)]
 struct APIPick {
     element: u16,
+    // 0 on the bench, 1 starting, 2 as captain and 3 as triple captain
+    #[serde(default)]
+    multiplier: u8,
 }
 
 #[derive(Deserialize, Debug)]
@@ -76,45 +135,200 @@ pub struct APISquad {
     entry_history: APIEntryHistory,
 }
 
-pub fn get_full_sorted_player_list() -> Result<Vec<Player>, Box<dyn std::error::Error>> {
-    let resp = reqwest::blocking::get(FANTASY_API_URL)?;
-    let resp_json: PlayerResponse = serde_json::from_str(&resp.text()?)?;
+#[derive(Deserialize, Debug)]
+struct APILiveStats {
+    total_points: i32,
+    #[serde(default)]
+    minutes: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct APILiveElement {
+    id: u16,
+    stats: APILiveStats,
+}
+
+#[derive(Deserialize, Debug)]
+struct APILive {
+    elements: Vec<APILiveElement>,
+}
+
+#[derive(Deserialize, Debug)]
+struct APIMatch {
+    minutes: u32,
+}
+
+// A manager's place in a classic league
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LeagueEntry {
+    pub entry: u32, // The manager's user-id
+    pub entry_name: String,
+    pub player_name: String,
+    pub rank: u32,
+    pub total: i32,
+}
+
+#[derive(Deserialize, Debug)]
+struct APILeagueInfo {
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct APIStandingsPage {
+    has_next: bool,
+    results: Vec<LeagueEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+struct APILeague {
+    league: APILeagueInfo,
+    standings: APIStandingsPage,
+}
+
+// A manager's picks for a gameweek, as a squad and the multiplier of each player
+// by id, with their points so far
+pub struct EntryPicks {
+    pub squad: Squad,
+    pub multipliers: HashMap<u16, u8>,
+    pub total_points: i32,
+}
 
-    let mut result: Vec<Player> = resp_json.elements.iter().map(|p| p.to_player()).collect();
-    result.sort_by(|b, a| a.metric().partial_cmp(&b.metric()).unwrap());
-    Ok(result)
+#[derive(Deserialize, Debug)]
+struct APIElementSummary {
+    history: Vec<APIMatch>,
+}
+
+// Raw bootstrap-static response, which is what snapshots store
+pub fn get_bootstrap_static() -> Result<String, Box<dyn std::error::Error>> {
+    Ok(reqwest::blocking::get(FANTASY_API_URL)?.text()?)
+}
+
+pub fn save_snapshot(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(path, get_bootstrap_static()?)?;
+    Ok(())
+}
+
+// Points each player scored in a gameweek and the minutes they played, by player id
+pub fn get_live_points(
+    gameweek: u8,
+) -> Result<HashMap<u16, (i32, u32)>, Box<dyn std::error::Error>> {
+    let resp = reqwest::blocking::get(format!(
+        "https://fantasy.premierleague.com/api/event/{}/live/",
+        gameweek
+    ))?;
+    let resp_json: APILive = serde_json::from_str(&resp.text()?)?;
+    Ok(resp_json
+        .elements
+        .iter()
+        .map(|e| (e.id, (e.stats.total_points, e.stats.minutes)))
+        .collect())
+}
+
+// Minutes the player played in each of their matches this season, oldest first
+pub fn get_match_minutes(player_id: u16) -> Result<Vec<u32>, Box<dyn std::error::Error>> {
+    let resp = reqwest::blocking::get(format!(
+        "https://fantasy.premierleague.com/api/element-summary/{}/",
+        player_id
+    ))?;
+    let resp_json: APIElementSummary = serde_json::from_str(&resp.text()?)?;
+    Ok(resp_json.history.iter().map(|m| m.minutes).collect())
+}
+
+// Fixtures that haven't been played yet
+pub fn get_fixtures() -> Result<Vec<Fixture>, Box<dyn std::error::Error>> {
+    Ok(serde_json::from_str(
+        &reqwest::blocking::get(FIXTURES_URL)?.text()?,
+    )?)
+}
+
+// Points the manager scored in a gameweek, before hits
+pub fn get_gameweek_points(user_id: u32, gameweek: u8) -> Result<i32, Box<dyn std::error::Error>> {
+    let resp = reqwest::blocking::get(format!(
+        "https://fantasy.premierleague.com/api/entry/{}/event/{}/picks/",
+        user_id, gameweek
+    ))?;
+    let resp_json: APISquad = serde_json::from_str(&resp.text()?)?;
+    Ok(resp_json.entry_history.points)
 }
 
 pub fn get_my_squad(
     user_id: u32,
     current_gameweek: u8,
-    full_player_list: &Vec<Player>,
+    full_player_list: &[Player],
 ) -> Result<Squad, Box<dyn std::error::Error>> {
+    Ok(get_entry_picks(user_id, current_gameweek, full_player_list)?.squad)
+}
+
+pub fn get_entry_picks(
+    user_id: u32,
+    gameweek: u8,
+    full_player_list: &[Player],
+) -> Result<EntryPicks, Box<dyn std::error::Error>> {
     let resp = reqwest::blocking::get(format!(
         "https://fantasy.premierleague.com/api/entry/{}/event/{}/picks/",
-        user_id, current_gameweek
+        user_id, gameweek
     ))?;
     let resp_json: APISquad = serde_json::from_str(&resp.text()?)?;
-    let mut current_squad = Squad::new(f32::INFINITY);
-    for player in full_player_list {
-        for pick in &resp_json.picks {
-            if pick.element == player.id {
-                current_squad
-                    .try_add_player(&player)
-                    .expect("error adding players from api to squad");
-                break;
+    Ok(resp_json.to_entry_picks(full_player_list))
+}
+
+impl APISquad {
+    fn to_entry_picks(&self, full_player_list: &[Player]) -> EntryPicks {
+        let mut squad = Squad::new(Money::UNLIMITED);
+        for player in full_player_list {
+            for pick in &self.picks {
+                if pick.element == player.id {
+                    squad
+                        .try_add_player(player)
+                        .expect("error adding players from api to squad");
+                    break;
+                }
             }
         }
+        squad.set_max_cost(Money::from_tenths(self.entry_history.bank) + squad.total_cost());
+        EntryPicks {
+            squad,
+            multipliers: self
+                .picks
+                .iter()
+                .map(|p| (p.element, p.multiplier))
+                .collect(),
+            total_points: self.entry_history.total_points,
+        }
     }
-    current_squad.set_max_cost(resp_json.entry_history.bank / 10.0 + current_squad.total_cost());
-    Ok(current_squad)
+}
+
+// The classic league's name and its managers by rank, stopping once
+// `max_entries` are fetched or the standings run out
+pub fn get_league_standings(
+    league_id: u32,
+    max_entries: usize,
+) -> Result<(String, Vec<LeagueEntry>), Box<dyn std::error::Error>> {
+    let mut name = String::new();
+    let mut entries = Vec::new();
+    let mut page = 1;
+    while entries.len() < max_entries {
+        let resp = reqwest::blocking::get(format!(
+            "https://fantasy.premierleague.com/api/leagues-classic/{}/standings/?page_standings={}",
+            league_id, page
+        ))?;
+        let resp_json: APILeague = serde_json::from_str(&resp.text()?)?;
+        name = resp_json.league.name;
+        entries.extend(resp_json.standings.results);
+        if !resp_json.standings.has_next {
+            break;
+        }
+        page += 1;
+    }
+    entries.truncate(max_entries);
+    Ok((name, entries))
 }
 
 fn log_in_error(reason: &str) -> Result<(), Box<dyn std::error::Error>> {
-    Err(Box::new(Error::new(
-        ErrorKind::Other,
-        format!("Error logging in: {}", reason),
-    )))
+    Err(Box::new(Error::other(format!(
+        "Error logging in: {}",
+        reason
+    ))))
 }
 pub fn log_in(
     client: &reqwest::blocking::Client,
@@ -148,17 +362,17 @@ pub fn log_in(
     }
 }
 
-fn _single_transfer_payload(player_in: &Player, player_out: &Player) -> Transfer {
-    Transfer {
-        element_in: player_in.id.to_string(),
-        element_out: player_out.id.to_string(),
-        purchase_price: ((player_in.price * 10.0) as u32).to_string(),
-        selling_price: ((player_out.price * 10.0) as u32).to_string(),
+fn _single_transfer_payload(transfer: &Transfer) -> APITransfer {
+    APITransfer {
+        element_in: transfer.in_.id.to_string(),
+        element_out: transfer.out.id.to_string(),
+        purchase_price: transfer.in_.price.tenths().to_string(),
+        selling_price: transfer.out.price.tenths().to_string(),
     }
 }
 
 #[derive(Serialize, Debug)]
-struct Transfer {
+struct APITransfer {
     element_in: String,
     element_out: String,
     purchase_price: String,
@@ -166,56 +380,41 @@ struct Transfer {
 }
 
 fn _transfer_payload(
-    players_out: Vec<Player>,
-    players_in: Vec<Player>,
+    transfers: &[Transfer],
     user_id: u32,
     wildcard: bool,
     free_hit: bool,
     gameweek: u8,
-) -> TPI {
-    let mut payload = TPI {
+) -> TransferPayload {
+    TransferPayload {
         confirmed: "false".to_string(),
         event: gameweek.to_string(),
         entry: user_id.to_string(),
-        transfers: Vec::new(),
+        transfers: transfers.iter().map(_single_transfer_payload).collect(),
         wildcard: wildcard.to_string(),
         freehit: free_hit.to_string(),
-    };
-    for (player_in, player_out) in players_in.iter().zip(players_out.iter()) {
-        payload
-            .transfers
-            .push(_single_transfer_payload(player_in, player_out));
     }
-    payload
 }
 
 #[derive(Serialize, Debug)]
-struct TPI {
+struct TransferPayload {
     confirmed: String,
     event: String,
     entry: String,
-    transfers: Vec<Transfer>,
+    transfers: Vec<APITransfer>,
     wildcard: String,
     freehit: String,
 }
 
 pub fn transfer(
     client: &reqwest::blocking::Client,
-    players_out: Vec<Player>,
-    players_in: Vec<Player>,
+    transfers: &[Transfer],
     user_id: u32,
     wildcard: bool,
     free_hit: bool,
     gameweek: u8,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let params = _transfer_payload(
-        players_out,
-        players_in,
-        user_id,
-        wildcard,
-        free_hit,
-        gameweek,
-    );
+    let params = _transfer_payload(transfers, user_id, wildcard, free_hit, gameweek);
 
     let response = client
         .post(TRANSFER_URL)
@@ -235,43 +434,227 @@ pub fn transfer(
     }
 }
 
-struct Session {
+#[derive(Deserialize, Debug)]
+struct APIMyTeamPick {
+    element: u16,
+}
+
+#[derive(Deserialize, Debug)]
+struct APIMyTeamTransfers {
+    bank: i32,
+}
+
+#[derive(Deserialize, Debug)]
+struct APIMyTeam {
+    picks: Vec<APIMyTeamPick>,
+    transfers: APIMyTeamTransfers,
+}
+
+fn session_error(reason: &str) -> Box<dyn std::error::Error> {
+    Box::new(Error::other(format!(
+        "Error with saved session: {}",
+        reason
+    )))
+}
+
+#[derive(Deserialize, Debug)]
+struct APIMePlayer {
+    entry: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct APIMe {
+    player: Option<APIMePlayer>,
+}
+
+#[derive(Debug)]
+pub struct SessionExpired(String);
+impl fmt::Display for SessionExpired {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl std::error::Error for SessionExpired {}
+
+// Cookies from a browser export, as (url, `name=value; Domain=..; Path=..`) pairs.
+// Accepts the Netscape cookies.txt format most exporters produce, or a file of a
+// single `pl_profile=...` line or the bare value of the pl_profile cookie.
+// Anything else has no cookies.
+fn parse_cookie_file(contents: &str) -> Vec<(String, String)> {
+    let lines: Vec<&str> = contents
+        .lines()
+        .map(str::trim)
+        .map(|line| line.strip_prefix("#HttpOnly_").unwrap_or(line))
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+    if let [line] = lines.as_slice() {
+        if !line.contains('\t') {
+            return profile_cookie(line).into_iter().collect();
+        }
+    }
+    let mut cookies = Vec::new();
+    for line in lines {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 7 {
+            continue;
+        }
+        let (domain, path, name, value) = (fields[0], fields[2], fields[5], fields[6]);
+        if domain != "premierleague.com" && !domain.ends_with(".premierleague.com") {
+            continue;
+        }
+        cookies.push((
+            format!("https://{}{}", domain.trim_start_matches('.'), path),
+            format!("{}={}; Domain={}; Path={}", name, value, domain, path),
+        ));
+    }
+    cookies
+}
+
+// The pl_profile cookie from `pl_profile=<value>` or its bare value. Another
+// cookie's `name=value` or characters a cookie value can't hold give none; `=`
+// is only allowed as trailing base64 padding.
+fn profile_cookie(line: &str) -> Option<(String, String)> {
+    let value = line.strip_prefix("pl_profile=").unwrap_or(line);
+    let valid = |c: char| c.is_ascii_graphic() && !"\",;\\".contains(c);
+    if value.is_empty() || value.trim_end_matches('=').contains('=') || !value.chars().all(valid) {
+        return None;
+    }
+    Some((
+        String::from(FANTASY_URL),
+        format!(
+            "{}={}; Domain=.premierleague.com; Path=/",
+            PROFILE_COOKIE, value
+        ),
+    ))
+}
+
+pub struct Session {
     client: reqwest::blocking::Client,
+    cookies: Arc<Jar>,
     user_id: u32,
     last_gameweek: u8,
 }
 
 impl Session {
     pub fn new(user_id: u32, gameweek: u8) -> Result<Self, Box<dyn std::error::Error>> {
+        let cookies = Arc::new(Jar::default());
         Ok(Session {
             client: reqwest::blocking::Client::builder()
-                .cookie_store(true)
+                .cookie_provider(Arc::clone(&cookies))
                 .build()?,
-                user_id: user_id,
-                last_gameweek: gameweek,
+            cookies,
+            user_id,
+            last_gameweek: gameweek,
         })
     }
     pub fn log_in(&self, email: &str, password: &str) -> Result<(), Box<dyn std::error::Error>> {
         crate::api::log_in(&self.client, email, password)
     }
 
+    // Writes the session cookies as `url<TAB>name=value; name=value` lines, readable
+    // only by the owner since they grant the same access as the password.
+    pub fn save_cookies(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let mut file = crate::credentials::create_private_file(path)?;
+        for url in &[FANTASY_URL, USERS_URL] {
+            if let Some(cookies) = self.cookies.cookies(&url.parse()?) {
+                writeln!(file, "{}\t{}", url, cookies.to_str()?)?;
+            }
+        }
+        Ok(())
+    }
+    pub fn load_cookies(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        crate::credentials::check_permissions(path)?;
+        for line in fs::read_to_string(path)?.lines() {
+            let (url, cookies) = line
+                .split_once('\t')
+                .ok_or_else(|| session_error(&format!("malformed line in {}", path.display())))?;
+            let url = url.parse()?;
+            for cookie in cookies.split("; ") {
+                self.cookies.add_cookie_str(cookie, &url);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn import_cookies(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let cookies = parse_cookie_file(&fs::read_to_string(path)?);
+        if cookies.is_empty() {
+            return Err(session_error(&format!(
+                "no premierleague.com cookies in {}",
+                path.display()
+            )));
+        }
+        for (url, cookie) in cookies {
+            self.cookies.add_cookie_str(&cookie, &url.parse()?);
+        }
+        Ok(())
+    }
+
+    // Asks the server who we are, which fails once the cookies have expired
+    pub fn validate(&self) -> Result<u32, Box<dyn std::error::Error>> {
+        let resp = self.client.get(ME_URL).send()?;
+        let me: Option<APIMe> = if resp.status().is_success() {
+            serde_json::from_str(&resp.text()?).ok()
+        } else {
+            None
+        };
+        match me.and_then(|me| me.player).and_then(|player| player.entry) {
+            Some(entry) => Ok(entry),
+            None => Err(Box::new(SessionExpired(String::from(
+                "Session is not logged in, the cookies have expired or are not from fantasy.premierleague.com",
+            )))),
+        }
+    }
+
+    // Current picks and bank as seen by the logged in manager
+    pub fn get_my_squad(
+        &self,
+        full_player_list: &[Player],
+    ) -> Result<Squad, Box<dyn std::error::Error>> {
+        let resp = self
+            .client
+            .get(format!(
+                "https://fantasy.premierleague.com/api/my-team/{}/",
+                self.user_id
+            ))
+            .send()?;
+        if !resp.status().is_success() {
+            return Err(session_error(&format!(
+                "could not fetch team {} ({})",
+                self.user_id,
+                resp.status()
+            )));
+        }
+        let resp_json: APIMyTeam = serde_json::from_str(&resp.text()?)?;
+        let mut current_squad = Squad::new(Money::UNLIMITED);
+        for player in full_player_list {
+            if resp_json.picks.iter().any(|pick| pick.element == player.id) {
+                current_squad
+                    .try_add_player(player)
+                    .expect("error adding players from api to squad");
+            }
+        }
+        current_squad.set_max_cost(
+            Money::from_tenths(resp_json.transfers.bank) + current_squad.total_cost(),
+        );
+        Ok(current_squad)
+    }
+
     fn transfer_error(reason: &str) -> Result<(), Box<dyn std::error::Error>> {
-        Err(Box::new(Error::new(
-            ErrorKind::Other,
-            format!("Error requesting transfer: {}", reason),
-        )))
+        Err(Box::new(Error::other(format!(
+            "Error requesting transfer: {}",
+            reason
+        ))))
     }
     pub fn transfer(
         &self,
-        players_out: Vec<Player>,
-        players_in: Vec<Player>,
+        transfers: &[Transfer],
         wildcard: bool,
         free_hit: bool,
     ) -> Result<(), Box<dyn std::error::Error>> {
         crate::api::transfer(
             &self.client,
-            players_out,
-            players_in,
+            transfers,
             self.user_id,
             wildcard,
             free_hit,
@@ -282,11 +665,19 @@ impl Session {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::credentials::Credentials;
+
+    // Live tests against the fantasy server, run with
+    // `FPL_EMAIL=... FPL_PASSWORD=... cargo test -- --ignored`
+    fn env_credentials() -> Credentials {
+        Credentials::from_env().expect("FPL_EMAIL and FPL_PASSWORD must be set")
+    }
 
     #[test]
+    #[ignore = "requires network and FPL credentials"]
     fn test_transfer() {
-        let mut out_squad = Squad::new(100.0);
-        let mut in_squad = Squad::new(100.0);
+        let mut out_squad = Squad::new(Money::from_tenths(1000));
+        let mut in_squad = Squad::new(Money::from_tenths(1000));
 
         if false {
             out_squad.try_add_player(&salah_player()).unwrap();
@@ -296,40 +687,169 @@ mod tests {
             out_squad.try_add_player(&pogba_player()).unwrap();
         }
 
-        let out_copy = out_squad.clone();
-        let in_copy = in_squad.clone();
+        let transfers = in_squad.transfers_from(&out_squad);
 
+        let credentials = env_credentials();
         let session = Session::new(7597109, 4).unwrap();
-        session.log_in("polortiz4@hotmail.com", "password").unwrap();
+        session
+            .log_in(&credentials.email, &credentials.password)
+            .unwrap();
 
-        let res = session.transfer(out_copy.players, in_copy.players, false, false);
+        let res = session.transfer(&transfers, false, false);
         res.unwrap();
     }
 
     #[test]
+    #[ignore = "requires network"]
     fn test_get_my_squad() {
-        let list = get_full_sorted_player_list().unwrap();
+        let list = PlayerResponse::from_json(&get_bootstrap_static().unwrap())
+            .unwrap()
+            .sorted_players();
         let squad = get_my_squad(2367749, 1, &list).unwrap();
         let copy = squad.clone();
-        print!("{}", squad.changed_squad(&copy));
+        print!("{}", squad.changed_squad(&copy, 0.0));
     }
 
     #[test]
+    #[ignore = "requires network and FPL credentials"]
     fn test_log_in() {
-        let mut client = reqwest::blocking::Client::new();
-        let bad_call_result = log_in(&mut client, "polortiz4@hotmail.com", "pasfsword");
+        let credentials = env_credentials();
+        let client = reqwest::blocking::Client::new();
+        let bad_call_result = log_in(&client, &credentials.email, "pasfsword");
         assert_eq!(
             "Error logging in: credentials",
             bad_call_result.unwrap_err().to_string()
         );
-        log_in(&mut client, "polortiz4@hotmail.com", "password").unwrap();
+        log_in(&client, &credentials.email, &credentials.password).unwrap();
+    }
+
+    #[test]
+    fn test_parse_cookie_file() {
+        let exported = "# Netscape HTTP Cookie File\n\
+            .premierleague.com\tTRUE\t/\tTRUE\t1700000000\tpl_profile\tabc\n\
+            #HttpOnly_fantasy.premierleague.com\tFALSE\t/\tTRUE\t0\tsessionid\txyz\n\
+            .example.com\tTRUE\t/\tFALSE\t0\ttracker\t1\n\
+            .notpremierleague.com\tTRUE\t/\tFALSE\t0\ttracker\t2\n\
+            stray line\n";
+        assert_eq!(
+            parse_cookie_file(exported),
+            vec![
+                (
+                    String::from("https://premierleague.com/"),
+                    String::from("pl_profile=abc; Domain=.premierleague.com; Path=/")
+                ),
+                (
+                    String::from("https://fantasy.premierleague.com/"),
+                    String::from("sessionid=xyz; Domain=fantasy.premierleague.com; Path=/")
+                ),
+            ]
+        );
+
+        let expected = vec![(
+            String::from(FANTASY_URL),
+            String::from("pl_profile=abc; Domain=.premierleague.com; Path=/"),
+        )];
+        assert_eq!(parse_cookie_file("pl_profile=abc\n"), expected);
+        assert_eq!(parse_cookie_file("abc"), expected);
+        let padded = parse_cookie_file("YWJj==\n");
+        assert_eq!(
+            padded[0].1,
+            "pl_profile=YWJj==; Domain=.premierleague.com; Path=/"
+        );
+        assert!(parse_cookie_file("# nothing here\n").is_empty());
+
+        // Other formats and cookies aren't taken for the profile
+        assert!(parse_cookie_file("csrftoken=xyz\n").is_empty());
+        assert!(parse_cookie_file("pl_profile=abc\ncsrftoken=xyz\n").is_empty());
+        assert!(parse_cookie_file(
+            ".premierleague.com TRUE / TRUE 0 pl_profile abc\n\
+             .premierleague.com TRUE / TRUE 0 sessionid xyz\n"
+        )
+        .is_empty());
+        assert!(
+            parse_cookie_file(r#"[{"domain": ".premierleague.com", "name": "pl_profile"}]"#)
+                .is_empty()
+        );
+        assert!(parse_cookie_file(r#"[{"name":"pl_profile","value":"abc"}]"#).is_empty());
+    }
+
+    #[test]
+    fn test_session_cookies() {
+        let session = Session::new(1, 1).unwrap();
+        let url = FANTASY_URL.parse().unwrap();
+        session.cookies.add_cookie_str("pl_profile=abc", &url);
+        session.cookies.add_cookie_str("csrftoken=xyz", &url);
+
+        let path = std::env::temp_dir().join(format!("rust_epl_session_{}", std::process::id()));
+        session.save_cookies(&path).unwrap();
+
+        let restored = Session::new(1, 1).unwrap();
+        restored.load_cookies(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        // The jar does not keep insertion order
+        let sorted_cookies = |session: &Session| {
+            let header = session.cookies.cookies(&url).unwrap();
+            let mut cookies: Vec<String> = header
+                .to_str()
+                .unwrap()
+                .split("; ")
+                .map(String::from)
+                .collect();
+            cookies.sort();
+            cookies
+        };
+        assert_eq!(
+            sorted_cookies(&session),
+            vec!["csrftoken=xyz", "pl_profile=abc"]
+        );
+        assert_eq!(sorted_cookies(&restored), sorted_cookies(&session));
+        assert!(restored
+            .cookies
+            .cookies(&USERS_URL.parse().unwrap())
+            .is_none());
+    }
+
+    #[test]
+    fn test_parse_league_and_picks() {
+        let league: APILeague = serde_json::from_str(
+            r#"{"league": {"id": 314, "name": "Office"},
+                "standings": {"has_next": false, "page": 1, "results": [
+                    {"id": 9, "entry": 2367749, "entry_name": "Eleven", "player_name": "Sam Doe",
+                     "rank": 1, "last_rank": 2, "event_total": 60, "total": 1200}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(league.league.name, "Office");
+        assert!(!league.standings.has_next);
+        assert_eq!(
+            league.standings.results,
+            vec![LeagueEntry {
+                entry: 2367749,
+                entry_name: String::from("Eleven"),
+                player_name: String::from("Sam Doe"),
+                rank: 1,
+                total: 1200,
+            }]
+        );
+
+        let picks: APISquad = serde_json::from_str(
+            r#"{"picks": [{"element": 272, "position": 1, "multiplier": 2, "is_captain": true},
+                          {"element": 233, "position": 12, "multiplier": 0, "is_captain": false}],
+                "entry_history": {"bank": 15, "points": 60, "total_points": 1200}}"#,
+        )
+        .unwrap();
+        let entry = picks.to_entry_picks(&[salah_player(), pogba_player()]);
+        assert_eq!(entry.squad.players.len(), 2);
+        assert_eq!(entry.squad.max_cost(), Money::from_tenths(77 + 125 + 15));
+        assert_eq!(entry.multipliers[&272], 2);
+        assert_eq!(entry.multipliers[&233], 0);
+        assert_eq!(entry.total_points, 1200);
     }
 
     fn pogba_player() -> Player {
         Player::new(
             1.0,
             1.0,
-            7.7,
+            Money::from_tenths(77),
             String::from("Pogba"),
             Position::MID,
             272,
@@ -343,7 +863,7 @@ mod tests {
         Player::new(
             1.0,
             1.0,
-            12.5,
+            Money::from_tenths(125),
             String::from("Salah"),
             Position::MID,
             233,
@@ -0,0 +1,332 @@
+use crate::lineup::{FormationRules, N_POSITIONS};
+use crate::player::{Player, Position};
+
+const MAX_ORDERED: usize = 6; // Outfield bench players whose every order is tried
+const MIN_PROBABILITY: f32 = 1e-4; // Combinations of who plays less likely than this are left out
+
+// The FPL auto-sub rules on positions (by `Position::index`) and who played: the
+// goalkeeper on the bench only comes on for the goalkeeper, and every outfield
+// starter who didn't play, in lineup order, is replaced by the first outfield
+// player on the bench who played, hasn't come on yet and keeps the formation
+// valid. Gives (starter, bench player) index pairs.
+pub fn substitute(
+    starters: &[(usize, bool)],
+    bench: &[(usize, bool)],
+    rules: &FormationRules,
+) -> Vec<(usize, usize)> {
+    let gk = Position::GK.index();
+    let mut counts = [0; N_POSITIONS];
+    starters.iter().for_each(|(p, _)| counts[*p] += 1);
+    let mut used = vec![false; bench.len()];
+    let mut subs = Vec::new();
+    for (i, (position, played)) in starters.iter().enumerate() {
+        if *played {
+            continue;
+        }
+        let fits = |p: usize| {
+            if (p == gk) != (*position == gk) {
+                return false;
+            }
+            let mut after = counts;
+            after[*position] -= 1;
+            after[p] += 1;
+            (0..N_POSITIONS)
+                .all(|q| after[q] >= rules.min[q].min(counts[q]) && after[q] <= rules.max[q])
+        };
+        let sub = bench
+            .iter()
+            .enumerate()
+            .find(|(j, (p, played))| *played && !used[*j] && fits(*p));
+        if let Some((j, (p, _))) = sub {
+            used[j] = true;
+            counts[*position] -= 1;
+            counts[*p] += 1;
+            subs.push((i, j));
+        }
+    }
+    subs
+}
+
+// Who comes on for whom when `played` says who played
+pub fn auto_subs(
+    starters: &[Player],
+    bench: &[Player],
+    rules: &FormationRules,
+    played: impl Fn(&Player) -> bool,
+) -> Vec<(Player, Player)> {
+    let slots = |players: &[Player]| -> Vec<(usize, bool)> {
+        players
+            .iter()
+            .map(|p| (p.position.index(), played(p)))
+            .collect()
+    };
+    substitute(&slots(starters), &slots(bench), rules)
+        .into_iter()
+        .map(|(i, j)| (starters[i].clone(), bench[j].clone()))
+        .collect()
+}
+
+// Points a player is worth when they play, their metric being what they're
+// worth counting the chance they don't
+fn points_when_playing(player: &Player) -> f32 {
+    match player.chance_of_playing() {
+        chance if chance > 0.0 => player.metric() / chance,
+        _ => 0.0,
+    }
+}
+
+// Expected auto-sub points over the ways players `i..` can turn out, given the
+// combination so far in `slots` (starters then bench) and how likely it is
+fn expected_from(
+    i: usize,
+    probability: f32,
+    chances: &[f32],
+    slots: &mut [(usize, bool)],
+    n_starters: usize,
+    points: &[f32],
+    rules: &FormationRules,
+) -> f32 {
+    if probability < MIN_PROBABILITY {
+        return 0.0;
+    }
+    if i == chances.len() {
+        let (starters, bench) = slots.split_at(n_starters);
+        if starters.iter().all(|(_, played)| *played) {
+            return 0.0;
+        }
+        let subs = substitute(starters, bench, rules);
+        return probability * subs.iter().map(|(_, j)| points[*j]).sum::<f32>();
+    }
+    let chance = chances[i];
+    let mut expected = 0.0;
+    if chance > 0.0 {
+        slots[i].1 = true;
+        expected += expected_from(
+            i + 1,
+            probability * chance,
+            chances,
+            slots,
+            n_starters,
+            points,
+            rules,
+        );
+    }
+    if chance < 1.0 {
+        slots[i].1 = false;
+        expected += expected_from(
+            i + 1,
+            probability * (1.0 - chance),
+            chances,
+            slots,
+            n_starters,
+            points,
+            rules,
+        );
+    }
+    expected
+}
+
+// Points the bench is expected to add through auto-subs, over the combinations
+// of who plays, each weighted by how likely it is
+pub fn expected_sub_points(starters: &[Player], bench: &[Player], rules: &FormationRules) -> f32 {
+    let players: Vec<&Player> = starters.iter().chain(bench.iter()).collect();
+    let chances: Vec<f32> = players
+        .iter()
+        .map(|p| p.chance_of_playing().clamp(0.0, 1.0))
+        .collect();
+    let mut slots: Vec<(usize, bool)> = players
+        .iter()
+        .map(|p| (p.position.index(), false))
+        .collect();
+    let points: Vec<f32> = bench.iter().map(points_when_playing).collect();
+    expected_from(0, 1.0, &chances, &mut slots, starters.len(), &points, rules)
+}
+
+// Every order of `items`
+fn orders<T: Clone>(items: &[T]) -> Vec<Vec<T>> {
+    if items.len() <= 1 {
+        return vec![items.to_vec()];
+    }
+    let mut all = Vec::new();
+    for i in 0..items.len() {
+        let mut rest = items.to_vec();
+        let first = rest.remove(i);
+        for mut order in orders(&rest) {
+            order.insert(0, first.clone());
+            all.push(order);
+        }
+    }
+    all
+}
+
+// The bench order with the most expected auto-sub points, goalkeepers first as
+// the game requires. Outfield players start highest metric first and only move
+// for more points, or stay that way when there are too many to try every order.
+pub fn best_bench_order(
+    starters: &[Player],
+    bench: &[Player],
+    rules: &FormationRules,
+) -> Vec<Player> {
+    let by_metric = |players: &mut Vec<Player>| {
+        players.sort_by(|a, b| {
+            b.metric()
+                .partial_cmp(&a.metric())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+    };
+    let (mut goalkeepers, mut outfield): (Vec<Player>, Vec<Player>) = bench
+        .iter()
+        .cloned()
+        .partition(|p| p.position == Position::GK);
+    by_metric(&mut goalkeepers);
+    by_metric(&mut outfield);
+    if outfield.len() > MAX_ORDERED {
+        goalkeepers.append(&mut outfield);
+        return goalkeepers;
+    }
+    let mut best: Option<(f32, Vec<Player>)> = None;
+    for order in orders(&outfield) {
+        let mut candidate = goalkeepers.clone();
+        candidate.extend(order);
+        let points = expected_sub_points(starters, &candidate, rules);
+        if best.as_ref().is_none_or(|(top, _)| points > *top) {
+            best = Some((points, candidate));
+        }
+    }
+    best.map(|(_, order)| order).unwrap_or(goalkeepers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(id: u16, position: Position, metric: f32, chance: f32) -> Player {
+        Player::with_chance(id, position, 1, metric, chance)
+    }
+
+    // A 1-4-4-2 lineup of regulars and a bench of a goalkeeper, a defender, a
+    // midfielder and a forward
+    fn squad(chances: [f32; 15]) -> (Vec<Player>, Vec<Player>) {
+        let positions = [
+            Position::GK,
+            Position::DEF,
+            Position::DEF,
+            Position::DEF,
+            Position::DEF,
+            Position::MID,
+            Position::MID,
+            Position::MID,
+            Position::MID,
+            Position::FWD,
+            Position::FWD,
+            Position::GK,
+            Position::DEF,
+            Position::MID,
+            Position::FWD,
+        ];
+        let mut players: Vec<Player> = positions
+            .iter()
+            .zip(chances.iter())
+            .enumerate()
+            .map(|(i, (position, chance))| player(i as u16 + 1, position.clone(), 4.0, *chance))
+            .collect();
+        let bench = players.split_off(11);
+        (players, bench)
+    }
+
+    #[test]
+    fn test_auto_subs() {
+        let rules = FormationRules::default();
+        let (starters, bench) = squad([1.0; 15]);
+        let missing = |ids: &'static [u16]| move |p: &Player| !ids.contains(&p.id);
+        let ids = |subs: Vec<(Player, Player)>| -> Vec<(u16, u16)> {
+            subs.iter().map(|(o, i)| (o.id, i.id)).collect()
+        };
+        // The goalkeeper for the goalkeeper, the first outfield player for anyone else
+        assert_eq!(
+            ids(auto_subs(&starters, &bench, &rules, missing(&[1, 7]))),
+            vec![(1, 12), (7, 13)]
+        );
+        // The second forward out can only make way for a forward
+        assert_eq!(
+            ids(auto_subs(&starters, &bench, &rules, missing(&[10, 11]))),
+            vec![(10, 13), (11, 15)]
+        );
+        // A bench player who didn't play is skipped, and a defender can't make way
+        // when that leaves fewer than three
+        assert_eq!(
+            ids(auto_subs(
+                &starters,
+                &bench,
+                &rules,
+                missing(&[2, 3, 4, 13])
+            )),
+            vec![(2, 14)]
+        );
+        // Nobody comes on when nobody on the bench keeps the formation valid
+        let mut three_at_the_back = starters.clone();
+        three_at_the_back.remove(4);
+        three_at_the_back.push(player(16, Position::MID, 4.0, 1.0));
+        assert_eq!(
+            ids(auto_subs(&three_at_the_back, &bench, &rules, missing(&[2]))),
+            vec![(2, 13)]
+        );
+        assert!(auto_subs(&three_at_the_back, &bench[2..], &rules, missing(&[2])).is_empty());
+    }
+
+    #[test]
+    fn test_expected_sub_points() {
+        let rules = FormationRules::default();
+        let (starters, bench) = squad([1.0; 15]);
+        assert_eq!(expected_sub_points(&starters, &bench, &rules), 0.0);
+
+        // One doubtful midfielder: the first outfield bench player comes on half the time
+        let mut chances = [1.0; 15];
+        chances[6] = 0.5;
+        let (starters, bench) = squad(chances);
+        assert!((expected_sub_points(&starters, &bench, &rules) - 2.0).abs() < 1e-5);
+
+        // When the first bench player is doubtful too, the next one covers for them
+        chances[12] = 0.5;
+        let (starters, bench) = squad(chances);
+        assert!(
+            (expected_sub_points(&starters, &bench, &rules) - 0.5 * (0.5 * 4.0 + 0.5 * 4.0)).abs()
+                < 1e-5
+        );
+    }
+
+    #[test]
+    fn test_best_bench_order() {
+        let rules = FormationRules::default();
+        // A doubtful midfielder is best covered by a defender who rarely plays but
+        // scores well, with the midfielder behind him for when he doesn't
+        let mut chances = [1.0; 15];
+        chances[6] = 0.5;
+        let (starters, mut bench) = squad(chances);
+        bench[1] = player(13, Position::DEF, 5.0, 0.2);
+        bench[2] = player(14, Position::MID, 3.0, 1.0);
+        bench[3] = player(15, Position::FWD, 2.0, 1.0);
+        let order = best_bench_order(&starters, &bench, &rules);
+        assert_eq!(
+            order.iter().map(|p| p.id).collect::<Vec<_>>(),
+            vec![12, 13, 14, 15]
+        );
+        assert!((expected_sub_points(&starters, &order, &rules) - 1.7).abs() < 1e-5);
+        let by_metric = [
+            bench[0].clone(),
+            bench[2].clone(),
+            bench[3].clone(),
+            bench[1].clone(),
+        ];
+        assert!((expected_sub_points(&starters, &by_metric, &rules) - 1.5).abs() < 1e-5);
+
+        // Nobody doubtful keeps the highest metric first
+        let (starters, mut bench) = squad([1.0; 15]);
+        bench[3] = player(15, Position::FWD, 6.0, 1.0);
+        let order = best_bench_order(&starters, &bench, &rules);
+        assert_eq!(
+            order.iter().map(|p| p.id).collect::<Vec<_>>(),
+            vec![12, 15, 13, 14]
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use crate::lineup::N_POSITIONS;
+use crate::player::{Metric, Position};
+use serde::{Deserialize, Deserializer};
+use std::str::FromStr;
+
+// `expected_points=0.6,projected=0.4`
+fn parse_weights(s: &str) -> Result<Vec<(Metric, f32)>, String> {
+    let mut weights: Vec<(Metric, f32)> = Vec::new();
+    for pair in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (metric, weight) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("expected `metric=weight`, got `{}`", pair))?;
+        let metric: Metric = metric.trim().parse()?;
+        let weight: f32 = weight
+            .trim()
+            .parse()
+            .ok()
+            .filter(|w: &f32| w.is_finite() && *w >= 0.0)
+            .ok_or_else(|| format!("weight of {} must be a number, not negative", metric))?;
+        if weights.iter().any(|(m, _)| *m == metric) {
+            return Err(format!("{} is weighted twice", metric));
+        }
+        weights.push((metric, weight));
+    }
+    if weights.iter().map(|(_, w)| w).sum::<f32>() <= 0.0 {
+        return Err(format!("no positive weights in `{}`", s.trim()));
+    }
+    Ok(weights)
+}
+
+// A metric made of the weighted average of other metrics, with other weights
+// for the positions that have their own
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blend {
+    positions: [Vec<(Metric, f32)>; N_POSITIONS],
+}
+
+impl Blend {
+    pub fn weights(&self, position: &Position) -> &[(Metric, f32)] {
+        &self.positions[position.index()]
+    }
+
+    // Every metric some position uses, in the order they were first given
+    pub fn metrics(&self) -> Vec<Metric> {
+        let mut metrics = Vec::new();
+        for (metric, _) in self.positions.iter().flatten() {
+            if !metrics.contains(metric) {
+                metrics.push(*metric);
+            }
+        }
+        metrics
+    }
+
+    pub fn value(&self, position: &Position, value_of: impl Fn(Metric) -> f32) -> f32 {
+        let weights = self.weights(position);
+        let total: f32 = weights.iter().map(|(_, w)| w).sum();
+        weights.iter().map(|(m, w)| w * value_of(*m)).sum::<f32>() / total
+    }
+}
+
+impl FromStr for Blend {
+    type Err = String;
+    // Weights for every position, then `;` and a position's own weights, e.g.
+    // `expected_points=0.6,form=0.4; FWD: projected=1`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut default = None;
+        let mut positions: [Option<Vec<(Metric, f32)>>; N_POSITIONS] = Default::default();
+        for part in s.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+            match part.split_once(':') {
+                Some((position, weights)) => {
+                    let position: Position = position.trim().parse()?;
+                    let own = &mut positions[position.index()];
+                    if own.is_some() {
+                        return Err(format!("{} is weighted twice", position));
+                    }
+                    *own = Some(parse_weights(weights)?);
+                }
+                None if default.is_none() => default = Some(parse_weights(part)?),
+                None => {
+                    return Err(String::from(
+                        "only one set of weights can be for every position",
+                    ))
+                }
+            }
+        }
+        let mut blend = Blend {
+            positions: Default::default(),
+        };
+        let names = ["GK", "DEF", "MID", "FWD"];
+        for (i, own) in positions.iter_mut().enumerate() {
+            blend.positions[i] = own
+                .take()
+                .or_else(|| default.clone())
+                .ok_or_else(|| format!("no weights for {}", names[i]))?;
+        }
+        Ok(blend)
+    }
+}
+
+// Written like on the command line in config files
+impl<'de> Deserialize<'de> for Blend {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let blend: Blend = "expected_points=3, form=1; FWD: projected=1"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            blend.weights(&Position::MID),
+            &[(Metric::ExpectedPoints, 3.0), (Metric::Form, 1.0)]
+        );
+        assert_eq!(blend.weights(&Position::FWD), &[(Metric::Projected, 1.0)]);
+        assert_eq!(
+            blend.metrics(),
+            vec![Metric::ExpectedPoints, Metric::Form, Metric::Projected]
+        );
+
+        let by_position = "GK: form=1; DEF: form=1; MID: form=1; FWD: expected_points=1";
+        assert!(by_position.parse::<Blend>().is_ok());
+        let error = |s: &str| s.parse::<Blend>().unwrap_err();
+        assert_eq!(error("GK: form=1"), "no weights for DEF");
+        assert_eq!(error("form=1,form=2"), "form is weighted twice");
+        assert_eq!(error("form=0"), "no positive weights in `form=0`");
+        assert_eq!(
+            error("form=-1"),
+            "weight of form must be a number, not negative"
+        );
+        assert_eq!(error("form"), "expected `metric=weight`, got `form`");
+        assert!(error("points=1").starts_with("unknown metric `points`"));
+        assert!(error("form=1; GOAL: form=1").starts_with("unknown position `GOAL`"));
+    }
+
+    #[test]
+    fn test_value() {
+        let blend: Blend = "expected_points=3,form=1; GK: form=1".parse().unwrap();
+        let value_of = |metric| match metric {
+            Metric::ExpectedPoints => 6.0,
+            Metric::Form => 2.0,
+            _ => 0.0,
+        };
+        assert_eq!(blend.value(&Position::MID, value_of), 5.0);
+        assert_eq!(blend.value(&Position::GK, value_of), 2.0);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::player::Player;
+use std::cmp::Ordering;
+
+pub const CAPTAINCY_OPTIONS: usize = 3; // Best options reports show
+
+// A captain and vice-captain pair and what the armband is expected to score: the
+// captain's points times the multiplier when they play, otherwise the vice's
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptainOption {
+    pub captain: Player,
+    pub vice_captain: Option<Player>,
+    pub expected_points: f32,
+    pub stdev: f32,
+    // What the options are ranked by, the expected points plus the upside preference
+    // times the spread
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Outcome {
+    chance: f32, // Of playing
+    points: f32, // When playing
+}
+
+impl Outcome {
+    // A player's points when they play are taken to spread like a Poisson
+    // distribution around their metric over their chance of playing
+    fn of(player: &Player) -> Self {
+        let chance = player.chance_of_playing().clamp(0.0, 1.0);
+        Outcome {
+            chance,
+            points: if chance > 0.0 {
+                player.metric().max(0.0) / chance
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+fn option(
+    captain: &Player,
+    vice_captain: Option<&Player>,
+    captain_multiplier: f32,
+    upside: f32,
+) -> CaptainOption {
+    let c = Outcome::of(captain);
+    let v = vice_captain.map_or(
+        Outcome {
+            chance: 0.0,
+            points: 0.0,
+        },
+        Outcome::of,
+    );
+    let v_chance = (1.0 - c.chance) * v.chance; // The vice only wears the armband when the captain blanks
+    let mean = c.chance * c.points + v_chance * v.points;
+    let square =
+        c.chance * (c.points * c.points + c.points) + v_chance * (v.points * v.points + v.points);
+    let expected_points = captain_multiplier * mean;
+    let stdev = captain_multiplier * (square - mean * mean).max(0.0).sqrt();
+    CaptainOption {
+        captain: captain.clone(),
+        vice_captain: vice_captain.cloned(),
+        expected_points,
+        stdev,
+        score: expected_points + upside * stdev,
+    }
+}
+
+// Every captain and vice-captain pair among `starters`, best score first. A
+// positive `upside` prefers captains whose points spread more, a negative one
+// safer captains. Ties go to the higher metric captain, then vice.
+pub fn options(starters: &[Player], captain_multiplier: f32, upside: f32) -> Vec<CaptainOption> {
+    let mut options = Vec::new();
+    for captain in starters {
+        let vices: Vec<Option<&Player>> = match starters.len() {
+            1 => vec![None],
+            _ => starters
+                .iter()
+                .filter(|v| *v != captain)
+                .map(Some)
+                .collect(),
+        };
+        for vice_captain in vices {
+            options.push(option(captain, vice_captain, captain_multiplier, upside));
+        }
+    }
+    let metric = |p: Option<&Player>| p.map_or(f32::MIN, Player::metric);
+    options.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| {
+                metric(Some(&b.captain))
+                    .partial_cmp(&metric(Some(&a.captain)))
+                    .unwrap_or(Ordering::Equal)
+            })
+            .then_with(|| {
+                metric(b.vice_captain.as_ref())
+                    .partial_cmp(&metric(a.vice_captain.as_ref()))
+                    .unwrap_or(Ordering::Equal)
+            })
+    });
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::Position;
+
+    // A player scoring `points` when they play, which they do with `chance`
+    fn player(id: u16, points: f32, chance: f32) -> Player {
+        Player::with_chance(id, Position::MID, id as u8, points, chance)
+    }
+
+    fn ids(option: &CaptainOption) -> (u16, Option<u16>) {
+        (
+            option.captain.id,
+            option.vice_captain.as_ref().map(|p| p.id),
+        )
+    }
+
+    #[test]
+    fn test_options() {
+        // A doubtful star, a sure regular and a sure fringe player
+        let starters = vec![
+            player(1, 10.0, 0.6),
+            player(2, 7.0, 1.0),
+            player(3, 3.0, 1.0),
+        ];
+        let options = options(&starters, 2.0, 0.0);
+        assert_eq!(options.len(), 6);
+        // 0.6 * 10 + 0.4 * 7, the vice covering for the star, then 0.6 * 10 + 0.4 * 3
+        // beat the sure 7, whose vice doesn't matter
+        assert_eq!(ids(&options[0]), (1, Some(2)));
+        assert!((options[0].expected_points - 2.0 * 8.8).abs() < 1e-4);
+        assert_eq!(ids(&options[1]), (1, Some(3)));
+        assert_eq!(ids(&options[2]), (2, Some(1)));
+        assert!((options[2].expected_points - 14.0).abs() < 1e-4);
+        assert_eq!(ids(&options[3]), (2, Some(3)));
+
+        // A sure captain's vice doesn't matter, ties go to the better vice
+        let sure = vec![
+            player(1, 5.0, 1.0),
+            player(2, 6.0, 1.0),
+            player(3, 6.0, 1.0),
+        ];
+        let ranked = super::options(&sure, 2.0, 0.0);
+        assert_eq!(ranked[0].captain.metric(), 6.0);
+        assert_eq!(ranked[0].vice_captain.as_ref().unwrap().metric(), 6.0);
+        assert_eq!(ranked[0].stdev, 2.0 * 6.0f32.sqrt());
+
+        // One starter has no vice, and an empty lineup has no options rather than a panic
+        let alone = super::options(&starters[..1], 2.0, 0.0);
+        assert_eq!(ids(&alone[0]), (1, None));
+        assert!((alone[0].expected_points - 12.0).abs() < 1e-4);
+        assert!(super::options(&[], 2.0, 0.0).is_empty());
+    }
+
+    #[test]
+    fn test_upside() {
+        // A sure 8, or a coin flip on 14 with the sure 8 as vice: 11 on average but
+        // spread wider
+        let starters = vec![player(1, 8.0, 1.0), player(2, 14.0, 0.5)];
+        let by_mean = options(&starters, 2.0, 0.0);
+        assert_eq!(ids(&by_mean[0]), (2, Some(1)));
+        assert!((by_mean[0].expected_points - 22.0).abs() < 1e-4);
+        assert!((by_mean[0].stdev - 2.0 * 20.0f32.sqrt()).abs() < 1e-4);
+        assert_eq!(options(&starters, 2.0, 1.0)[0].captain.id, 2);
+        let safe = options(&starters, 2.0, -2.0);
+        assert_eq!(ids(&safe[0]), (1, Some(2)));
+        assert!(safe[0].stdev < by_mean[0].stdev);
+    }
+}
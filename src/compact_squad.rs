@@ -0,0 +1,323 @@
+use crate::lineup::{self, Lineup, N_POSITIONS};
+use crate::money::Money;
+use crate::player::Player;
+use crate::rules::GameRules;
+use crate::squad::Squad;
+
+const N_TEAM_IDS: usize = 256;
+
+// The players the optimizer picks from, with what the search reads about each
+// one copied out so it never has to touch (or clone) a `Player`
+pub struct PlayerPool {
+    players: Vec<Player>,
+    price: Vec<Money>,
+    metric: Vec<f32>,
+    ownership: Vec<f32>,
+    slot: Vec<usize>,
+    team: Vec<u8>,
+    in_current: Vec<bool>, // In the squad the transfers are made from
+}
+
+impl PlayerPool {
+    pub fn new(players: Vec<Player>, current: Option<&Squad>) -> Self {
+        PlayerPool {
+            price: players.iter().map(|p| p.price).collect(),
+            metric: players.iter().map(|p| p.metric()).collect(),
+            ownership: players.iter().map(|p| p.ownership()).collect(),
+            slot: players.iter().map(|p| p.position.index()).collect(),
+            team: players.iter().map(|p| p.team.id()).collect(),
+            in_current: players
+                .iter()
+                .map(|p| current.is_some_and(|c| c.has_player(p)))
+                .collect(),
+            players,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.players.len()
+    }
+    pub fn player(&self, i: usize) -> &Player {
+        &self.players[i]
+    }
+    pub fn price(&self, i: usize) -> Money {
+        self.price[i]
+    }
+    pub fn metric(&self, i: usize) -> f32 {
+        self.metric[i]
+    }
+    pub fn in_current(&self, i: usize) -> bool {
+        self.in_current[i]
+    }
+}
+
+// A squad as indices into a `PlayerPool`, keeping its cost, team counts and the
+// metrics of each position up to date as players come and go, so checking a
+// player and valuing the lineup don't rebuild anything. Players are removed in
+// the reverse order they were added, like the optimizer's search does.
+#[derive(Clone)]
+pub struct CompactSquad {
+    max_cost: Money,
+    rules: GameRules,
+    picked: Vec<usize>, // Pool indices, in the order they were added
+    members: Vec<u64>,  // Bitset of `picked`
+    total_cost: Money,
+    metrics: [Vec<f32>; N_POSITIONS], // Highest first
+    team_count: [u8; N_TEAM_IDS],
+    n_current: usize, // Picked players that are in the current squad
+}
+
+impl CompactSquad {
+    // An empty squad with the budget and rules of `squad`
+    pub fn new(pool: &PlayerPool, squad: &Squad) -> Self {
+        let rules = *squad.rules();
+        let capacity = rules.squad_size;
+        CompactSquad {
+            max_cost: squad.max_cost(),
+            rules,
+            picked: Vec::with_capacity(rules.squad_len()),
+            members: vec![0; pool.len().div_ceil(64)],
+            total_cost: Money::ZERO,
+            metrics: [
+                Vec::with_capacity(capacity[0]),
+                Vec::with_capacity(capacity[1]),
+                Vec::with_capacity(capacity[2]),
+                Vec::with_capacity(capacity[3]),
+            ],
+            team_count: [0; N_TEAM_IDS],
+            n_current: 0,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.picked.len()
+    }
+    pub fn indices(&self) -> &[usize] {
+        &self.picked
+    }
+    pub fn contains(&self, i: usize) -> bool {
+        self.members[i / 64] & (1 << (i % 64)) != 0
+    }
+    pub fn total_cost(&self) -> Money {
+        self.total_cost
+    }
+    pub fn max_cost(&self) -> Money {
+        self.max_cost
+    }
+    pub fn positions_full(&self) -> bool {
+        self.len() == self.rules.squad_len()
+    }
+    pub fn number_of_changes(&self) -> usize {
+        self.len() - self.n_current
+    }
+
+    // The same rules as `Squad::try_add_player`
+    pub fn fits(&self, pool: &PlayerPool, i: usize) -> bool {
+        let slot = pool.slot[i];
+        (self.team_count[pool.team[i] as usize] as usize) < self.rules.max_per_team
+            && !self.contains(i)
+            && self.total_cost + pool.price[i] <= self.max_cost
+            && self.metrics[slot].len() < self.rules.squad_size[slot]
+    }
+    pub fn add(&mut self, pool: &PlayerPool, i: usize) {
+        debug_assert!(self.fits(pool, i));
+        self.picked.push(i);
+        self.members[i / 64] |= 1 << (i % 64);
+        self.total_cost += pool.price[i];
+        self.team_count[pool.team[i] as usize] += 1;
+        if pool.in_current[i] {
+            self.n_current += 1;
+        }
+        let metrics = &mut self.metrics[pool.slot[i]];
+        let at = metrics.partition_point(|&m| m >= pool.metric[i]);
+        metrics.insert(at, pool.metric[i]);
+    }
+    // Removes the player added last
+    pub fn pop(&mut self, pool: &PlayerPool) {
+        let i = self
+            .picked
+            .pop()
+            .expect("Popped a player from an empty squad");
+        self.members[i / 64] &= !(1 << (i % 64));
+        self.total_cost -= pool.price[i];
+        self.team_count[pool.team[i] as usize] -= 1;
+        if pool.in_current[i] {
+            self.n_current -= 1;
+        }
+        let metrics = &mut self.metrics[pool.slot[i]];
+        let at = metrics
+            .iter()
+            .rposition(|&m| m == pool.metric[i])
+            .expect("Removed metric is not in the squad");
+        metrics.remove(at);
+    }
+
+    fn best_lineup(&self) -> Lineup {
+        lineup::best_lineup(
+            [
+                &self.metrics[0],
+                &self.metrics[1],
+                &self.metrics[2],
+                &self.metrics[3],
+            ],
+            &self.rules.formation,
+            self.rules.captain_multiplier,
+        )
+    }
+    // Metric of `Squad::best_starter_lineup`
+    pub fn lineup_metric(&self) -> f32 {
+        self.best_lineup().metric
+    }
+    // The same lineup valued by `Player::differential_metric`, the captain by
+    // their own one
+    pub fn differential_metric(&self, pool: &PlayerPool, weight: f32) -> f32 {
+        let lineup = self.best_lineup();
+        let mut by_position: [Vec<usize>; N_POSITIONS] = Default::default();
+        for &i in &self.picked {
+            by_position[pool.slot[i]].push(i);
+        }
+        let value = |i: usize| pool.metric[i] * (1.0 - weight * pool.ownership[i]);
+        let mut total = 0.0;
+        let mut captain: Option<usize> = None;
+        for (players, n) in by_position.iter_mut().zip(lineup.starters.iter()) {
+            players.sort_by(|a, b| {
+                pool.metric[*b]
+                    .partial_cmp(&pool.metric[*a])
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            for &i in &players[..*n] {
+                total += value(i);
+                if captain.is_none_or(|c| pool.metric[i] > pool.metric[c]) {
+                    captain = Some(i);
+                }
+            }
+        }
+        total + captain.map_or(0.0, |c| (self.rules.captain_multiplier - 1.0) * value(c))
+    }
+
+    pub fn to_squad(&self, pool: &PlayerPool) -> Squad {
+        let mut squad = Squad::with_rules(self.max_cost, self.rules);
+        for &i in &self.picked {
+            squad
+                .try_add_player(pool.player(i))
+                .expect("A compact squad broke the squad rules");
+        }
+        squad
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::Position;
+
+    fn players() -> Vec<Player> {
+        let positions = [
+            (Position::GK, 2),
+            (Position::DEF, 5),
+            (Position::MID, 5),
+            (Position::FWD, 3),
+        ];
+        let mut players = Vec::new();
+        for (position, n) in positions.iter() {
+            for _ in 0..*n {
+                let id = players.len() as u16 + 1;
+                let metric = ((id * 7) % 16) as f32 + 0.5;
+                players.push(Player::with_price(
+                    id,
+                    position.clone(),
+                    (id % 6) as u8 + 1,
+                    50,
+                    metric,
+                ));
+            }
+        }
+        players
+    }
+
+    #[test]
+    fn test_matches_squad() {
+        let players = players();
+        let mut current = Squad::new(Money::UNLIMITED);
+        players[..5]
+            .iter()
+            .for_each(|p| current.try_add_player(p).unwrap());
+        let pool = PlayerPool::new(players.clone(), Some(&current));
+
+        let mut squad = Squad::new(Money::from_tenths(750));
+        let mut compact = CompactSquad::new(&pool, &squad);
+        for (i, p) in players.iter().enumerate() {
+            assert!(compact.fits(&pool, i));
+            squad.try_add_player(p).unwrap();
+            compact.add(&pool, i);
+            assert_eq!(compact.total_cost(), squad.total_cost());
+            let changes = squad
+                .players
+                .iter()
+                .filter(|p| !current.has_player(p))
+                .count();
+            assert_eq!(compact.number_of_changes(), changes);
+        }
+        assert!(compact.positions_full());
+        assert_eq!(
+            compact.lineup_metric(),
+            squad.best_starter_lineup().total_metric(2.0)
+        );
+        assert_eq!(compact.to_squad(&pool), squad);
+        assert_eq!(compact.to_squad(&pool).players, squad.players);
+
+        // Popping puts back what the player took
+        let full = compact.clone();
+        compact.pop(&pool);
+        assert!(!compact.contains(14));
+        assert_eq!(compact.total_cost(), Money::from_tenths(700));
+        compact.add(&pool, 14);
+        assert_eq!(compact.lineup_metric(), full.lineup_metric());
+        assert_eq!(compact.indices(), full.indices());
+    }
+
+    #[test]
+    fn test_differential_metric() {
+        let mut players = players();
+        for (i, player) in players.iter_mut().enumerate() {
+            player.selected_by_percent = (i * 6) as f32;
+        }
+        let pool = PlayerPool::new(players.clone(), None);
+        let mut squad = Squad::new(Money::UNLIMITED);
+        let mut compact = CompactSquad::new(&pool, &squad);
+        for (i, p) in players.iter().enumerate() {
+            squad.try_add_player(p).unwrap();
+            compact.add(&pool, i);
+        }
+        let lineup = squad.best_starter_lineup();
+        assert!(
+            (compact.differential_metric(&pool, 0.7) - lineup.differential_metric(2.0, 0.7)).abs()
+                < 1e-4
+        );
+        assert!(compact.differential_metric(&pool, 0.7) < compact.lineup_metric());
+        assert_eq!(
+            compact.differential_metric(&pool, 0.0),
+            compact.lineup_metric()
+        );
+    }
+
+    #[test]
+    fn test_fits() {
+        let players = vec![
+            Player::with_price(1, Position::GK, 1, 50, 1.0),
+            Player::with_price(2, Position::GK, 1, 50, 1.0),
+            Player::with_price(3, Position::GK, 2, 50, 1.0),
+            Player::with_price(4, Position::DEF, 1, 50, 1.0),
+            Player::with_price(5, Position::DEF, 1, 50, 1.0),
+            Player::with_price(6, Position::DEF, 3, 101, 1.0),
+        ];
+        let pool = PlayerPool::new(players, None);
+        let mut compact = CompactSquad::new(&pool, &Squad::new(Money::from_tenths(250)));
+        compact.add(&pool, 0);
+        assert!(!compact.fits(&pool, 0)); // Already in the squad
+        compact.add(&pool, 1);
+        assert!(!compact.fits(&pool, 2)); // No goalkeeper places left
+        compact.add(&pool, 3);
+        assert!(!compact.fits(&pool, 4)); // Three players from team 1
+        assert!(!compact.fits(&pool, 5)); // Costs 10.1 with 10.0 left
+        assert_eq!(compact.number_of_changes(), 3);
+    }
+}
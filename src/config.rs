@@ -0,0 +1,1308 @@
+use crate::blend::Blend;
+use crate::credentials;
+use crate::league;
+use crate::player::Metric;
+use crate::query::PlayerQuery;
+use crate::report::OutputFormat;
+use crate::rules::{self, GameRules};
+use crate::simulation::SimulationSettings;
+use crate::top_squad::SearchObjective;
+use clap::{load_yaml, App, ArgMatches};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const ENV_PREFIX: &str = "RUST_EPL_";
+
+#[derive(Debug, PartialEq)]
+pub struct ConfigError(String);
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error in configuration: {}", self.0)
+    }
+}
+impl Error for ConfigError {}
+
+// One layer of settings. Every layer (config file profile, environment, command
+// line) fills in what it knows and the layers are merged on top of each other.
+#[derive(Debug, Default, Clone, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub user_id: Option<u32>,
+    pub email: Option<String>,
+    pub metric: Option<Metric>,
+    pub blend: Option<Blend>,
+    pub top_n_players: Option<usize>,
+    pub min_player_metric: Option<f32>,
+    pub free_transfers: Option<usize>,
+    pub transfer_cost: Option<f32>,
+    pub bench_point_value: Option<f32>,
+    pub keep_players: Option<Vec<u16>>,
+    pub exclude_players: Option<Vec<u16>>,
+    pub squad_file: Option<PathBuf>,
+    pub data_file: Option<PathBuf>,
+    pub projections_file: Option<PathBuf>,
+    pub model_horizon: Option<usize>,
+    pub minutes_risk: Option<bool>,
+    pub exclude_unavailable: Option<bool>,
+    pub captain_upside: Option<f32>,
+    pub objective: Option<SearchObjective>,
+    pub simulations: Option<usize>,
+    pub seed: Option<u64>,
+    pub team_correlation: Option<f32>,
+    pub risk_aversion: Option<f32>,
+    pub ownership_weight: Option<f32>,
+    pub simulation_margin: Option<f32>,
+    pub rules: Option<RulesSetting>,
+}
+
+// The rules a profile plays under, a preset's name or a table of rules
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum RulesSetting {
+    Preset(String),
+    Table(GameRules),
+}
+
+impl RulesSetting {
+    fn resolve(&self) -> Result<GameRules, ConfigError> {
+        let rules = match self {
+            RulesSetting::Preset(name) => GameRules::preset(name).ok_or_else(|| {
+                ConfigError(format!(
+                    "unknown rules `{}`, expected one of: {}",
+                    name,
+                    rules::PRESETS.join(", ")
+                ))
+            })?,
+            RulesSetting::Table(rules) => *rules,
+        };
+        rules
+            .validate()
+            .map_err(|e| ConfigError(format!("rules: {}", e)))?;
+        Ok(rules)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    default_profile: Option<String>,
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+fn parse_value<T: FromStr>(name: &str, value: &str) -> Result<T, ConfigError>
+where
+    T::Err: fmt::Display,
+{
+    value
+        .trim()
+        .parse::<T>()
+        .map_err(|e| ConfigError(format!("invalid value `{}` for {}: {}", value, name, e)))
+}
+
+fn parse_list(name: &str, value: &str) -> Result<Vec<u16>, ConfigError> {
+    value
+        .split(',')
+        .filter(|id| !id.trim().is_empty())
+        .map(|id| parse_value(name, id))
+        .collect()
+}
+
+// Matches of the whole subcommand path, looked up from the innermost subcommand
+// out, so options can be given wherever they are declared
+struct ArgChain<'a> {
+    matches: Vec<&'a ArgMatches<'a>>,
+}
+
+impl<'a> ArgChain<'a> {
+    fn new(root: &'a ArgMatches<'a>) -> Self {
+        let mut matches = vec![root];
+        while let (_, Some(sub)) = matches[matches.len() - 1].subcommand() {
+            matches.push(sub);
+        }
+        matches.reverse();
+        ArgChain { matches }
+    }
+    fn value_of(&self, arg: &str) -> Option<&'a str> {
+        self.matches.iter().find_map(|m| m.value_of(arg))
+    }
+    fn is_present(&self, arg: &str) -> bool {
+        self.matches.iter().any(|m| m.is_present(arg))
+    }
+}
+
+// What the binary was asked to do
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Optimize {
+        save_plan: Option<PathBuf>,
+    },
+    SquadShow,
+    SquadExport {
+        file: PathBuf,
+    },
+    Players(PlayerQuery),
+    PlayersExport {
+        file: PathBuf,
+    },
+    Transfer {
+        players_out: Vec<u16>,
+        players_in: Vec<u16>,
+        plan: Option<PathBuf>, // Submit a plan saved by `optimize --save-plan` instead
+        wildcard: bool,
+        free_hit: bool,
+        yes: bool, // Submit without asking
+    },
+    Lineup,
+    Simulate {
+        rival: Option<u32>, // Manager whose squad to compare against
+    },
+    League {
+        league_id: u32,
+        rivals: usize,    // Managers to fetch from the top of the standings
+        transfers: usize, // Transfers to list
+    },
+    Backtest {
+        snapshot: PathBuf,
+    },
+    Snapshot {
+        file: PathBuf,
+    },
+}
+
+impl Command {
+    fn from_matches(m: &ArgMatches, args: &ArgChain) -> Result<Command, ConfigError> {
+        let command = match m.subcommand() {
+            ("optimize", Some(sub)) => Command::Optimize {
+                save_plan: sub.value_of("save_plan").map(PathBuf::from),
+            },
+            ("squad", Some(sub)) => match sub.subcommand() {
+                ("show", _) => Command::SquadShow,
+                ("export", Some(export)) => Command::SquadExport {
+                    file: PathBuf::from(export.value_of("file").unwrap_or_default()),
+                },
+                (other, _) => return Err(unknown_command("squad", Some(other))),
+            },
+            ("players", Some(sub)) => match sub.subcommand() {
+                ("list", _) => Command::Players(player_query(args)?),
+                ("export", Some(export)) => Command::PlayersExport {
+                    file: PathBuf::from(export.value_of("file").unwrap_or_default()),
+                },
+                (other, _) => return Err(unknown_command("players", Some(other))),
+            },
+            ("transfer", Some(sub)) => {
+                let players_out = parse_list("--out", sub.value_of("players_out").unwrap_or(""))?;
+                let players_in = parse_list("--in", sub.value_of("players_in").unwrap_or(""))?;
+                if players_out.len() != players_in.len() {
+                    return Err(ConfigError(format!(
+                        "--out has {} players but --in has {}",
+                        players_out.len(),
+                        players_in.len()
+                    )));
+                }
+                Command::Transfer {
+                    players_out,
+                    players_in,
+                    plan: sub.value_of("plan").map(PathBuf::from),
+                    wildcard: sub.is_present("wildcard"),
+                    free_hit: sub.is_present("free_hit"),
+                    yes: sub.is_present("yes"),
+                }
+            }
+            ("lineup", _) => Command::Lineup,
+            ("simulate", _) => Command::Simulate {
+                rival: optional_value(args, "rival", "--rival")?,
+            },
+            ("league", Some(sub)) => Command::League {
+                league_id: parse_value("league", sub.value_of("league").unwrap_or_default())?,
+                rivals: optional_value(args, "rivals", "--rivals")?
+                    .unwrap_or(league::DEFAULT_RIVALS),
+                transfers: optional_value(args, "transfers", "--transfers")?
+                    .unwrap_or(league::DEFAULT_TRANSFERS),
+            },
+            ("backtest", Some(sub)) => Command::Backtest {
+                snapshot: PathBuf::from(sub.value_of("snapshot").unwrap_or_default()),
+            },
+            ("snapshot", Some(sub)) => Command::Snapshot {
+                file: PathBuf::from(sub.value_of("file").unwrap_or_default()),
+            },
+            (other, _) => return Err(unknown_command("rust_epl", Some(other))),
+        };
+        Ok(command)
+    }
+}
+
+fn optional_value<T: FromStr>(
+    args: &ArgChain,
+    arg: &str,
+    flag: &str,
+) -> Result<Option<T>, ConfigError>
+where
+    T::Err: fmt::Display,
+{
+    args.value_of(arg).map(|v| parse_value(flag, v)).transpose()
+}
+
+fn player_query(args: &ArgChain) -> Result<PlayerQuery, ConfigError> {
+    let split = |arg: &str| -> Vec<String> {
+        args.value_of(arg)
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(String::from)
+            .collect()
+    };
+    let defaults = PlayerQuery::default();
+    Ok(PlayerQuery {
+        positions: split("position")
+            .iter()
+            .map(|p| parse_value("--position", p))
+            .collect::<Result<_, _>>()?,
+        teams: split("team"),
+        min_price: optional_value(args, "min_price", "--min-price")?,
+        max_price: optional_value(args, "max_price", "--max-price")?,
+        min_health: optional_value(args, "min_health", "--min-health")?,
+        min_metric: optional_value(args, "query_min_metric", "--min-metric")?,
+        max_metric: optional_value(args, "query_max_metric", "--max-metric")?,
+        max_difficulty: optional_value(args, "max_difficulty", "--max-difficulty")?,
+        horizon: optional_value(args, "horizon", "--horizon")?.unwrap_or(defaults.horizon),
+        sort: optional_value(args, "sort", "--sort")?.unwrap_or(defaults.sort),
+        reverse: args.is_present("reverse"),
+        count: optional_value(args, "count", "--count")?.unwrap_or(defaults.count),
+    })
+}
+
+fn unknown_command(parent: &str, command: Option<&str>) -> ConfigError {
+    ConfigError(format!(
+        "unknown command `{} {}`",
+        parent,
+        command.unwrap_or_default()
+    ))
+}
+
+impl Profile {
+    // Values in `other` win over the ones in `self`
+    pub fn merge(self, other: Profile) -> Profile {
+        // A metric and a blend replace each other, so a run can pick either
+        let (metric, blend) = if other.metric.is_some() || other.blend.is_some() {
+            (other.metric, other.blend)
+        } else {
+            (self.metric, self.blend)
+        };
+        // And so do the two ways of picking the players to search
+        let (top_n_players, min_player_metric) =
+            if other.top_n_players.is_some() || other.min_player_metric.is_some() {
+                (other.top_n_players, other.min_player_metric)
+            } else {
+                (self.top_n_players, self.min_player_metric)
+            };
+        Profile {
+            user_id: other.user_id.or(self.user_id),
+            email: other.email.or(self.email),
+            metric,
+            blend,
+            top_n_players,
+            min_player_metric,
+            free_transfers: other.free_transfers.or(self.free_transfers),
+            transfer_cost: other.transfer_cost.or(self.transfer_cost),
+            bench_point_value: other.bench_point_value.or(self.bench_point_value),
+            keep_players: other.keep_players.or(self.keep_players),
+            exclude_players: other.exclude_players.or(self.exclude_players),
+            squad_file: other.squad_file.or(self.squad_file),
+            data_file: other.data_file.or(self.data_file),
+            projections_file: other.projections_file.or(self.projections_file),
+            model_horizon: other.model_horizon.or(self.model_horizon),
+            minutes_risk: other.minutes_risk.or(self.minutes_risk),
+            exclude_unavailable: other.exclude_unavailable.or(self.exclude_unavailable),
+            captain_upside: other.captain_upside.or(self.captain_upside),
+            objective: other.objective.or(self.objective),
+            simulations: other.simulations.or(self.simulations),
+            seed: other.seed.or(self.seed),
+            team_correlation: other.team_correlation.or(self.team_correlation),
+            risk_aversion: other.risk_aversion.or(self.risk_aversion),
+            ownership_weight: other.ownership_weight.or(self.ownership_weight),
+            simulation_margin: other.simulation_margin.or(self.simulation_margin),
+            rules: other.rules.or(self.rules),
+        }
+    }
+
+    pub fn defaults() -> Profile {
+        Profile {
+            user_id: Some(2367749),
+            top_n_players: Some(20),
+            free_transfers: Some(1),
+            bench_point_value: Some(5.0),
+            model_horizon: Some(1),
+            simulations: Some(1000),
+            ..Profile::default()
+        }
+    }
+
+    // Reads `RUST_EPL_<FIELD>` variables through `var`, so tests don't need the real environment
+    pub fn from_vars<F: Fn(&str) -> Option<String>>(var: F) -> Result<Profile, ConfigError> {
+        let get = |field: &str| {
+            let name = format!("{}{}", ENV_PREFIX, field);
+            var(&name).map(|value| (name, value))
+        };
+        let mut profile = Profile::default();
+        if let Some((name, value)) = get("USER_ID") {
+            profile.user_id = Some(parse_value(&name, &value)?);
+        }
+        if let Some((_, value)) = get("EMAIL") {
+            profile.email = Some(value);
+        }
+        if let Some((name, value)) = get("METRIC") {
+            profile.metric = Some(parse_value(&name, &value)?);
+        }
+        if let Some((name, value)) = get("BLEND") {
+            profile.blend = Some(parse_value(&name, &value)?);
+        }
+        if let Some((name, value)) = get("TOP_N_PLAYERS") {
+            profile.top_n_players = Some(parse_value(&name, &value)?);
+        }
+        if let Some((name, value)) = get("MIN_PLAYER_METRIC") {
+            profile.min_player_metric = Some(parse_value(&name, &value)?);
+        }
+        if let Some((name, value)) = get("FREE_TRANSFERS") {
+            profile.free_transfers = Some(parse_value(&name, &value)?);
+        }
+        if let Some((name, value)) = get("TRANSFER_COST") {
+            profile.transfer_cost = Some(parse_value(&name, &value)?);
+        }
+        if let Some((name, value)) = get("BENCH_POINT_VALUE") {
+            profile.bench_point_value = Some(parse_value(&name, &value)?);
+        }
+        if let Some((name, value)) = get("KEEP_PLAYERS") {
+            profile.keep_players = Some(parse_list(&name, &value)?);
+        }
+        if let Some((name, value)) = get("EXCLUDE_PLAYERS") {
+            profile.exclude_players = Some(parse_list(&name, &value)?);
+        }
+        if let Some((_, value)) = get("SQUAD_FILE") {
+            profile.squad_file = Some(PathBuf::from(value));
+        }
+        if let Some((_, value)) = get("DATA_FILE") {
+            profile.data_file = Some(PathBuf::from(value));
+        }
+        if let Some((_, value)) = get("PROJECTIONS_FILE") {
+            profile.projections_file = Some(PathBuf::from(value));
+        }
+        if let Some((name, value)) = get("MODEL_HORIZON") {
+            profile.model_horizon = Some(parse_value(&name, &value)?);
+        }
+        if let Some((name, value)) = get("MINUTES_RISK") {
+            profile.minutes_risk = Some(parse_value(&name, &value)?);
+        }
+        if let Some((name, value)) = get("EXCLUDE_UNAVAILABLE") {
+            profile.exclude_unavailable = Some(parse_value(&name, &value)?);
+        }
+        if let Some((name, value)) = get("CAPTAIN_UPSIDE") {
+            profile.captain_upside = Some(parse_value(&name, &value)?);
+        }
+        if let Some((name, value)) = get("OBJECTIVE") {
+            profile.objective = Some(parse_value(&name, &value)?);
+        }
+        if let Some((name, value)) = get("SIMULATIONS") {
+            profile.simulations = Some(parse_value(&name, &value)?);
+        }
+        if let Some((name, value)) = get("SEED") {
+            profile.seed = Some(parse_value(&name, &value)?);
+        }
+        if let Some((name, value)) = get("TEAM_CORRELATION") {
+            profile.team_correlation = Some(parse_value(&name, &value)?);
+        }
+        if let Some((name, value)) = get("RISK_AVERSION") {
+            profile.risk_aversion = Some(parse_value(&name, &value)?);
+        }
+        if let Some((name, value)) = get("OWNERSHIP_WEIGHT") {
+            profile.ownership_weight = Some(parse_value(&name, &value)?);
+        }
+        if let Some((name, value)) = get("SIMULATION_MARGIN") {
+            profile.simulation_margin = Some(parse_value(&name, &value)?);
+        }
+        if let Some((_, value)) = get("RULES") {
+            profile.rules = Some(RulesSetting::Preset(value));
+        }
+        Ok(profile)
+    }
+
+    fn from_matches(m: &ArgChain) -> Result<Profile, ConfigError> {
+        let get = |arg: &str, flag: &'static str| m.value_of(arg).map(|value| (flag, value));
+        let mut profile = Profile::default();
+        if let Some((name, value)) = get("user_id", "--user-id") {
+            profile.user_id = Some(parse_value(name, value)?);
+        }
+        if let Some((_, value)) = get("email", "--email") {
+            profile.email = Some(value.to_string());
+        }
+        if let Some((name, value)) = get("metric", "--metric") {
+            profile.metric = Some(parse_value(name, value)?);
+        }
+        if let Some((name, value)) = get("blend", "--blend") {
+            profile.blend = Some(parse_value(name, value)?);
+        }
+        if let Some((name, value)) = get("top_n_players", "--top-n-players") {
+            profile.top_n_players = Some(parse_value(name, value)?);
+        }
+        if let Some((name, value)) = get("min_metric", "--min-player-metric") {
+            profile.min_player_metric = Some(parse_value(name, value)?);
+        }
+        if let Some((name, value)) = get("free_transfers", "--free-transfers") {
+            profile.free_transfers = Some(parse_value(name, value)?);
+        }
+        if let Some((name, value)) = get("transfer_cost", "--transfer-cost") {
+            profile.transfer_cost = Some(parse_value(name, value)?);
+        }
+        if let Some((name, value)) = get("bench_point_value", "--bench-point-value") {
+            profile.bench_point_value = Some(parse_value(name, value)?);
+        }
+        if let Some((name, value)) = get("keep", "--keep") {
+            profile.keep_players = Some(parse_list(name, value)?);
+        }
+        if let Some((name, value)) = get("exclude", "--exclude") {
+            profile.exclude_players = Some(parse_list(name, value)?);
+        }
+        if let Some((_, value)) = get("squad_file", "--squad-file") {
+            profile.squad_file = Some(PathBuf::from(value));
+        }
+        if let Some((_, value)) = get("data_file", "--data-file") {
+            profile.data_file = Some(PathBuf::from(value));
+        }
+        if let Some((_, value)) = get("projections", "--projections") {
+            profile.projections_file = Some(PathBuf::from(value));
+        }
+        if let Some((name, value)) = get("model_horizon", "--model-horizon") {
+            profile.model_horizon = Some(parse_value(name, value)?);
+        }
+        if m.is_present("minutes_risk") {
+            profile.minutes_risk = Some(true);
+        }
+        if m.is_present("exclude_unavailable") {
+            profile.exclude_unavailable = Some(true);
+        }
+        if let Some((name, value)) = get("captain_upside", "--captain-upside") {
+            profile.captain_upside = Some(parse_value(name, value)?);
+        }
+        if let Some((name, value)) = get("objective", "--objective") {
+            profile.objective = Some(parse_value(name, value)?);
+        }
+        if let Some((name, value)) = get("simulations", "--simulations") {
+            profile.simulations = Some(parse_value(name, value)?);
+        }
+        if let Some((name, value)) = get("seed", "--seed") {
+            profile.seed = Some(parse_value(name, value)?);
+        }
+        if let Some((name, value)) = get("team_correlation", "--team-correlation") {
+            profile.team_correlation = Some(parse_value(name, value)?);
+        }
+        if let Some((name, value)) = get("risk_aversion", "--risk-aversion") {
+            profile.risk_aversion = Some(parse_value(name, value)?);
+        }
+        if let Some((name, value)) = get("ownership_weight", "--ownership-weight") {
+            profile.ownership_weight = Some(parse_value(name, value)?);
+        }
+        if let Some((name, value)) = get("simulation_margin", "--simulation-margin") {
+            profile.simulation_margin = Some(parse_value(name, value)?);
+        }
+        Ok(profile)
+    }
+}
+
+// Picks `name`, or the file's default profile, out of a config file's contents
+pub fn load_profile(contents: &str, name: Option<&str>) -> Result<Profile, ConfigError> {
+    let ConfigFile {
+        default_profile,
+        profiles,
+    } = toml::from_str(contents).map_err(|e| ConfigError(format!("config file: {}", e)))?;
+    let name = match name.map(String::from).or(default_profile) {
+        Some(name) => name,
+        None => return Ok(Profile::default()),
+    };
+    profiles.get(&name).cloned().ok_or_else(|| {
+        ConfigError(format!(
+            "no profile named `{}`, available profiles: {}",
+            name,
+            profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+        ))
+    })
+}
+
+fn config_file_path(m: &ArgChain) -> Option<PathBuf> {
+    m.value_of("config")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os(format!("{}CONFIG", ENV_PREFIX)).map(PathBuf::from))
+        .or_else(|| {
+            credentials::config_dir()
+                .map(|dir| dir.join("config.toml"))
+                .filter(|path| path.exists())
+        })
+}
+
+fn read_profile(path: Option<&Path>, name: Option<&str>) -> Result<Profile, ConfigError> {
+    match path {
+        Some(path) => {
+            let contents = fs::read_to_string(path)
+                .map_err(|e| ConfigError(format!("could not read {}: {}", path.display(), e)))?;
+            load_profile(&contents, name)
+        }
+        None => match name {
+            Some(name) => Err(ConfigError(format!(
+                "profile `{}` requested but there is no config file",
+                name
+            ))),
+            None => Ok(Profile::default()),
+        },
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub command: Command,
+    pub gameweek: Option<u8>,
+    pub log_in: bool,
+    pub password: bool, // Prompt for the password instead of reading it from env/file
+    pub email: Option<String>,
+    pub credentials_file: Option<PathBuf>,
+    pub cookie_file: Option<PathBuf>,
+    pub session_file: Option<PathBuf>,
+    pub user_id: u32,
+    pub verbose: bool, // Not used yet
+    pub output: OutputFormat,
+    pub metric: Metric,
+    pub blend: Option<Blend>, // Ranks players by this instead of `metric`
+    pub top_n_player: Option<usize>,
+    pub free_transfers: usize,
+    pub squad_file: Option<PathBuf>, // Squad to use instead of pulling it from the server
+    pub data_file: Option<PathBuf>,  // Snapshot or CSV players to use instead of the live API
+    pub projections_file: Option<PathBuf>, // Per gameweek projections to give the players
+    pub model_horizon: usize,        // Gameweeks the expected points model projects
+    pub minutes_risk: bool,          // Scale expected points and projections by expected minutes
+    pub exclude_unavailable: bool, // Drop injured, suspended and unavailable players from the pool
+    pub captain_upside: f32, // Points per stdev of armband points, > 0 for upside, < 0 for safety
+    pub objective: SearchObjective, // What optimize ranks squads by
+    pub simulation: SimulationSettings,
+    pub risk_aversion: f32, // Points of mean given up per point of stdev with the risk objective
+    pub ownership_weight: f32, // How much of the owners' share the differential objective takes off
+    pub simulation_margin: f32, // Lineup metric behind the best a squad can be and still be simulated
+    pub min_player_metric: Option<f32>,
+    pub rules: GameRules, // The profile's rules, with its transfer_cost as the hit cost
+    pub bench_point_value: f32,
+    pub keep_players: Vec<u16>,
+    pub exclude_players: Vec<u16>,
+}
+impl Config {
+    // Whether players are ranked by `metric`, alone or in the blend
+    pub fn uses_metric(&self, metric: Metric) -> bool {
+        match &self.blend {
+            Some(blend) => blend.metrics().contains(&metric),
+            None => self.metric == metric,
+        }
+    }
+
+    // Defaults, then the config file profile, then RUST_EPL_* variables, then flags
+    pub fn parse_cli() -> Result<Config, ConfigError> {
+        let yaml = load_yaml!("cli.yml");
+        let matches = App::from(yaml).get_matches();
+        let m = ArgChain::new(&matches);
+
+        let profile_name = m
+            .value_of("profile")
+            .map(String::from)
+            .or_else(|| env::var(format!("{}PROFILE", ENV_PREFIX)).ok());
+        let profile = Profile::defaults()
+            .merge(read_profile(
+                config_file_path(&m).as_deref(),
+                profile_name.as_deref(),
+            )?)
+            .merge(Profile::from_vars(|name| env::var(name).ok())?)
+            .merge(Profile::from_matches(&m)?);
+
+        let session_file = if m.is_present("remember_session") {
+            credentials::config_dir().map(|dir| dir.join("session"))
+        } else {
+            None
+        };
+        let mut config = Config::from_profile(profile)?;
+        config.command = Command::from_matches(&matches, &m)?;
+        config.gameweek = m
+            .value_of("gameweek")
+            .map(|gweek| parse_value("--gameweek", gweek))
+            .transpose()?;
+        config.log_in =
+            m.is_present("log_in") || m.is_present("password") || m.is_present("cookie_file");
+        config.password = m.is_present("password");
+        config.credentials_file = m.value_of("credentials_file").map(PathBuf::from);
+        config.cookie_file = m.value_of("cookie_file").map(PathBuf::from);
+        config.session_file = session_file;
+        config.verbose = m.is_present("verbose");
+        config.output = m
+            .value_of("output")
+            .map(|output| parse_value("--output", output))
+            .transpose()?
+            .unwrap_or_default();
+        if let (Command::Optimize { .. }, Some(metric)) =
+            (&config.command, config.min_player_metric)
+        {
+            eprintln!(
+                "Choosing from players with metric > {} instead of using a top_n_players",
+                metric
+            );
+        }
+        Ok(config)
+    }
+
+    // Validates the merged settings, anything only given on the command line keeps its default
+    pub fn from_profile(profile: Profile) -> Result<Config, ConfigError> {
+        let missing = |name: &str| ConfigError(format!("missing value for {}", name));
+        let rules = match &profile.rules {
+            Some(setting) => setting.resolve()?,
+            None => GameRules::fpl_classic(),
+        };
+        let transfer_cost = profile.transfer_cost.unwrap_or(rules.hit_cost);
+        if transfer_cost < 0.0 {
+            return Err(ConfigError(format!(
+                "transfer_cost must not be negative, got {}",
+                transfer_cost
+            )));
+        }
+        let bench_point_value = profile
+            .bench_point_value
+            .ok_or_else(|| missing("bench_point_value"))?;
+        if bench_point_value < 0.0 {
+            return Err(ConfigError(format!(
+                "bench_point_value must not be negative, got {}",
+                bench_point_value
+            )));
+        }
+        if profile.top_n_players.is_some() && profile.min_player_metric.is_some() {
+            return Err(ConfigError(String::from(
+                "top_n_players and min_player_metric can't be used together",
+            )));
+        }
+        let top_n_player = match profile.min_player_metric {
+            Some(metric) if !metric.is_finite() => {
+                return Err(ConfigError(format!(
+                    "min_player_metric must be a number, got {}",
+                    metric
+                )))
+            }
+            Some(_) => None,
+            None => match profile.top_n_players {
+                Some(0) => return Err(ConfigError(String::from("top_n_players must be positive"))),
+                Some(n) => Some(n),
+                None => return Err(missing("top_n_players")),
+            },
+        };
+        if profile.metric.is_some() && profile.blend.is_some() {
+            return Err(ConfigError(String::from(
+                "metric and blend can't be used together",
+            )));
+        }
+        let model_horizon = match profile.model_horizon {
+            Some(0) => return Err(ConfigError(String::from("model_horizon must be positive"))),
+            Some(n) => n,
+            None => return Err(missing("model_horizon")),
+        };
+        let captain_upside = profile.captain_upside.unwrap_or(0.0);
+        if !captain_upside.is_finite() {
+            return Err(ConfigError(format!(
+                "captain_upside must be a number, got {}",
+                captain_upside
+            )));
+        }
+        let runs = match profile.simulations {
+            Some(0) => return Err(ConfigError(String::from("simulations must be positive"))),
+            Some(n) => n,
+            None => return Err(missing("simulations")),
+        };
+        let team_correlation = profile.team_correlation.unwrap_or(0.0);
+        if !(0.0..=1.0).contains(&team_correlation) {
+            return Err(ConfigError(format!(
+                "team_correlation must be between 0 and 1, got {}",
+                team_correlation
+            )));
+        }
+        let risk_aversion = profile.risk_aversion.unwrap_or(1.0);
+        if !risk_aversion.is_finite() {
+            return Err(ConfigError(format!(
+                "risk_aversion must be a number, got {}",
+                risk_aversion
+            )));
+        }
+        let ownership_weight = profile.ownership_weight.unwrap_or(1.0);
+        if !(0.0..=1.0).contains(&ownership_weight) {
+            return Err(ConfigError(format!(
+                "ownership_weight must be between 0 and 1, got {}",
+                ownership_weight
+            )));
+        }
+        let simulation_margin = profile.simulation_margin.unwrap_or(3.0);
+        if simulation_margin.is_nan() || simulation_margin < 0.0 {
+            return Err(ConfigError(format!(
+                "simulation_margin must not be negative, got {}",
+                simulation_margin
+            )));
+        }
+        let keep_players = profile.keep_players.unwrap_or_default();
+        let exclude_players = profile.exclude_players.unwrap_or_default();
+        if let Some(id) = keep_players.iter().find(|id| exclude_players.contains(id)) {
+            return Err(ConfigError(format!(
+                "player {} is both kept and excluded",
+                id
+            )));
+        }
+        Ok(Config {
+            command: Command::Optimize { save_plan: None },
+            gameweek: None,
+            log_in: false,
+            password: false,
+            email: profile.email,
+            credentials_file: None,
+            cookie_file: None,
+            session_file: None,
+            user_id: profile.user_id.ok_or_else(|| missing("user_id"))?,
+            verbose: false,
+            output: OutputFormat::default(),
+            // Imported projections are what the players are ranked by unless told otherwise
+            metric: match (profile.metric, &profile.projections_file) {
+                (Some(metric), _) => metric,
+                (None, Some(_)) => Metric::Projected,
+                (None, None) => Metric::default(),
+            },
+            blend: profile.blend,
+            top_n_player,
+            free_transfers: profile
+                .free_transfers
+                .ok_or_else(|| missing("free_transfers"))?,
+            squad_file: profile.squad_file,
+            data_file: profile.data_file,
+            projections_file: profile.projections_file,
+            model_horizon,
+            minutes_risk: profile.minutes_risk.unwrap_or(false),
+            exclude_unavailable: profile.exclude_unavailable.unwrap_or(false),
+            captain_upside,
+            objective: profile.objective.unwrap_or_default(),
+            simulation: SimulationSettings {
+                runs,
+                seed: profile.seed.unwrap_or(0),
+                team_correlation,
+            },
+            risk_aversion,
+            ownership_weight,
+            simulation_margin,
+            min_player_metric: profile.min_player_metric,
+            rules: GameRules {
+                hit_cost: transfer_cost,
+                ..rules
+            },
+            bench_point_value,
+            keep_players,
+            exclude_players,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+    use crate::player::Position;
+    use crate::query::SortKey;
+
+    const CONFIG_FILE: &str = r#"
+default_profile = "main"
+
+[profiles.main]
+user_id = 1234
+metric = "form"
+top_n_players = 30
+squad_file = "squads/main.toml"
+data_file = "players.csv"
+
+[profiles.hits]
+user_id = 5678
+transfer_cost = 2.0
+exclude_players = [233, 272]
+"#;
+
+    #[test]
+    fn test_load_profile() {
+        let main = load_profile(CONFIG_FILE, None).unwrap();
+        assert_eq!(main.user_id, Some(1234));
+        assert_eq!(main.metric, Some(Metric::Form));
+        assert_eq!(main.top_n_players, Some(30));
+        assert_eq!(main.squad_file, Some(PathBuf::from("squads/main.toml")));
+        assert_eq!(main.data_file, Some(PathBuf::from("players.csv")));
+
+        let hits = load_profile(CONFIG_FILE, Some("hits")).unwrap();
+        assert_eq!(hits.transfer_cost, Some(2.0));
+        assert_eq!(hits.exclude_players, Some(vec![233, 272]));
+
+        assert_eq!(
+            load_profile(CONFIG_FILE, Some("other")).unwrap_err(),
+            ConfigError(String::from(
+                "no profile named `other`, available profiles: hits, main"
+            ))
+        );
+        assert!(load_profile("[profiles.main]\nuser = 1\n", None)
+            .unwrap_err()
+            .to_string()
+            .contains("unknown field `user`"));
+    }
+
+    #[test]
+    fn test_layers() {
+        let env = |name: &str| match name {
+            "RUST_EPL_TOP_N_PLAYERS" => Some(String::from("40")),
+            "RUST_EPL_KEEP_PLAYERS" => Some(String::from("1, 2")),
+            "RUST_EPL_MINUTES_RISK" => Some(String::from("true")),
+            "RUST_EPL_EXCLUDE_UNAVAILABLE" => Some(String::from("false")),
+            "RUST_EPL_CAPTAIN_UPSIDE" => Some(String::from("-0.5")),
+            _ => None,
+        };
+        let profile = Profile::defaults()
+            .merge(load_profile(CONFIG_FILE, Some("hits")).unwrap())
+            .merge(Profile::from_vars(env).unwrap());
+        let config = Config::from_profile(profile).unwrap();
+        assert_eq!(config.user_id, 5678);
+        assert_eq!(config.rules.hit_cost, 2.0);
+        assert_eq!(config.bench_point_value, 5.0);
+        assert_eq!(config.top_n_player, Some(40));
+        assert_eq!(config.keep_players, vec![1, 2]);
+        assert_eq!(config.metric, Metric::ExpectedPoints);
+        assert!(config.minutes_risk);
+        assert!(!config.exclude_unavailable);
+        assert_eq!(config.captain_upside, -0.5);
+        let reckless = Profile {
+            captain_upside: Some(f32::INFINITY),
+            ..Profile::defaults()
+        };
+        assert!(Config::from_profile(reckless).is_err());
+        assert_eq!(config.objective, SearchObjective::Metric);
+        assert_eq!(
+            config.simulation,
+            SimulationSettings {
+                runs: 1000,
+                seed: 0,
+                team_correlation: 0.0
+            }
+        );
+        let simulated = |name: &str| match name {
+            "RUST_EPL_OBJECTIVE" => Some(String::from("simulated")),
+            "RUST_EPL_SEED" => Some(String::from("42")),
+            "RUST_EPL_TEAM_CORRELATION" => Some(String::from("1.5")),
+            _ => None,
+        };
+        let profile = Profile::defaults().merge(Profile::from_vars(simulated).unwrap());
+        assert_eq!(profile.objective, Some(SearchObjective::Simulated));
+        assert_eq!(profile.seed, Some(42));
+        assert!(Config::from_profile(profile).is_err());
+        let chasing = |name: &str| match name {
+            "RUST_EPL_OBJECTIVE" => Some(String::from("risk")),
+            "RUST_EPL_RISK_AVERSION" => Some(String::from("-0.5")),
+            "RUST_EPL_SIMULATION_MARGIN" => Some(String::from("inf")),
+            _ => None,
+        };
+        let config =
+            Config::from_profile(Profile::defaults().merge(Profile::from_vars(chasing).unwrap()))
+                .unwrap();
+        assert_eq!(config.objective, SearchObjective::Risk);
+        assert_eq!(config.risk_aversion, -0.5);
+        assert_eq!(config.ownership_weight, 1.0);
+        assert_eq!(config.simulation_margin, f32::INFINITY);
+        let negative_margin = Profile {
+            simulation_margin: Some(-1.0),
+            ..Profile::defaults()
+        };
+        assert!(Config::from_profile(negative_margin).is_err());
+        let overweight = Profile {
+            objective: Some(SearchObjective::Differential),
+            ownership_weight: Some(2.0),
+            ..Profile::defaults()
+        };
+        assert!(Config::from_profile(overweight).is_err());
+        assert!(
+            !Config::from_profile(Profile::defaults())
+                .unwrap()
+                .minutes_risk
+        );
+
+        let projected = Profile {
+            projections_file: Some(PathBuf::from("projections.csv")),
+            ..Profile::defaults()
+        };
+        assert_eq!(
+            Config::from_profile(projected.clone()).unwrap().metric,
+            Metric::Projected
+        );
+        let form = projected.merge(Profile {
+            metric: Some(Metric::Form),
+            ..Profile::default()
+        });
+        assert_eq!(Config::from_profile(form).unwrap().metric, Metric::Form);
+    }
+
+    #[test]
+    fn test_player_pool_layers() {
+        // A threshold in the file gives way to a count on the command line
+        let file =
+            load_profile("[profiles.main]\nmin_player_metric = 4.5\n", Some("main")).unwrap();
+        let yaml = load_yaml!("cli.yml");
+        let matches = App::from(yaml).get_matches_from(["rust_epl", "optimize", "-n", "30"]);
+        let cli = Profile::from_matches(&ArgChain::new(&matches)).unwrap();
+        let config =
+            Config::from_profile(Profile::defaults().merge(file.clone()).merge(cli)).unwrap();
+        assert_eq!(config.top_n_player, Some(30));
+        assert_eq!(config.min_player_metric, None);
+
+        // and a count in the environment to a threshold on the command line
+        let env = |name: &str| match name {
+            "RUST_EPL_TOP_N_PLAYERS" => Some(String::from("40")),
+            _ => None,
+        };
+        let yaml = load_yaml!("cli.yml");
+        let matches =
+            App::from(yaml).get_matches_from(["rust_epl", "optimize", "--min-player-metric", "3"]);
+        let cli = Profile::from_matches(&ArgChain::new(&matches)).unwrap();
+        let profile = Profile::defaults()
+            .merge(Profile::from_vars(env).unwrap())
+            .merge(cli);
+        let config = Config::from_profile(profile).unwrap();
+        assert_eq!(config.top_n_player, None);
+        assert_eq!(config.min_player_metric, Some(3.0));
+
+        // A later layer that picks neither keeps the threshold
+        let config = Config::from_profile(Profile::defaults().merge(file.merge(Profile {
+            user_id: Some(1),
+            ..Profile::default()
+        })))
+        .unwrap();
+        assert_eq!(config.min_player_metric, Some(4.5));
+    }
+
+    #[test]
+    fn test_rules() {
+        let config = Config::from_profile(Profile::defaults()).unwrap();
+        assert_eq!(config.rules, GameRules::fpl_classic());
+
+        let classic = load_profile("[profiles.main]\nrules = \"fpl_classic\"\n", Some("main"))
+            .unwrap()
+            .rules
+            .unwrap();
+        assert_eq!(classic.resolve(), Ok(GameRules::fpl_classic()));
+        assert!(RulesSetting::Preset(String::from("draft"))
+            .resolve()
+            .is_err());
+
+        // A table only gives what differs from FPL classic, transfer_cost still wins
+        let contents = r#"
+default_profile = "small"
+
+[profiles.small]
+transfer_cost = 2.0
+
+[profiles.small.rules]
+max_per_team = 2
+budget = 50.0
+hit_cost = 6.0
+"#;
+        let small = load_profile(contents, None).unwrap();
+        let config = Config::from_profile(Profile::defaults().merge(small.clone())).unwrap();
+        assert_eq!(config.rules.max_per_team, 2);
+        assert_eq!(config.rules.budget, Money::from_tenths(500));
+        assert_eq!(config.rules.hit_cost, 2.0);
+        assert_eq!(config.rules.squad_size, [2, 5, 5, 3]);
+        let own_hits = Profile {
+            transfer_cost: None,
+            ..small
+        };
+        let config = Config::from_profile(Profile::defaults().merge(own_hits)).unwrap();
+        assert_eq!(config.rules.hit_cost, 6.0);
+
+        let env = |name: &str| match name {
+            "RUST_EPL_RULES" => Some(String::from("fpl_classic")),
+            _ => None,
+        };
+        assert_eq!(
+            Profile::from_vars(env).unwrap().rules,
+            Some(RulesSetting::Preset(String::from("fpl_classic")))
+        );
+        let no_budget =
+            load_profile("[profiles.main.rules]\nbudget = 0.0\n", Some("main")).unwrap();
+        assert!(Config::from_profile(Profile::defaults().merge(no_budget)).is_err());
+    }
+
+    #[test]
+    fn test_blend() {
+        let env = |name: &str| match name {
+            "RUST_EPL_BLEND" => Some(String::from("expected_points=2,form=1; GK: form=1")),
+            _ => None,
+        };
+        let from_env = Profile::from_vars(env).unwrap();
+        let blended = Profile::defaults()
+            .merge(load_profile(CONFIG_FILE, None).unwrap())
+            .merge(from_env.clone());
+        assert_eq!(blended.metric, None);
+        let config = Config::from_profile(blended.clone()).unwrap();
+        assert_eq!(config.blend, from_env.blend);
+
+        // A later metric replaces the blend
+        let form = blended.merge(Profile {
+            metric: Some(Metric::Form),
+            ..Profile::default()
+        });
+        let config = Config::from_profile(form).unwrap();
+        assert_eq!((config.metric, &config.blend), (Metric::Form, &None));
+
+        let both = Profile {
+            metric: Some(Metric::Form),
+            ..from_env
+        };
+        assert!(Config::from_profile(Profile::defaults().merge(both)).is_err());
+        assert!(Profile::from_vars(|_| Some(String::from("form=x"))).is_err());
+        assert!(config.uses_metric(Metric::Form));
+        assert!(!config.uses_metric(Metric::Model));
+    }
+
+    fn command_of(args: &[&str]) -> Result<Command, ConfigError> {
+        let yaml = load_yaml!("cli.yml");
+        let matches = App::from(yaml).get_matches_from(args);
+        Command::from_matches(&matches, &ArgChain::new(&matches))
+    }
+
+    #[test]
+    fn test_commands() {
+        assert_eq!(
+            command_of(&["rust_epl", "optimize"]),
+            Ok(Command::Optimize { save_plan: None })
+        );
+        assert_eq!(
+            command_of(&["rust_epl", "optimize", "--save-plan", "plan.json"]),
+            Ok(Command::Optimize {
+                save_plan: Some(PathBuf::from("plan.json"))
+            })
+        );
+        assert_eq!(
+            command_of(&["rust_epl", "players", "export", "players.csv"]),
+            Ok(Command::PlayersExport {
+                file: PathBuf::from("players.csv")
+            })
+        );
+        assert_eq!(
+            command_of(&["rust_epl", "squad", "show"]),
+            Ok(Command::SquadShow)
+        );
+        assert_eq!(
+            command_of(&["rust_epl", "players", "list", "-c", "5"]),
+            Ok(Command::Players(PlayerQuery {
+                count: 5,
+                ..PlayerQuery::default()
+            }))
+        );
+        assert_eq!(
+            command_of(&[
+                "rust_epl",
+                "players",
+                "list",
+                "--position",
+                "def,mid",
+                "--team",
+                "Arsenal,11",
+                "--max-price",
+                "5.0",
+                "--max-difficulty",
+                "2.5",
+                "--sort",
+                "value",
+            ]),
+            Ok(Command::Players(PlayerQuery {
+                positions: vec![Position::DEF, Position::MID],
+                teams: vec![String::from("Arsenal"), String::from("11")],
+                max_price: Some(Money::from_tenths(50)),
+                max_difficulty: Some(2.5),
+                sort: SortKey::Value,
+                ..PlayerQuery::default()
+            }))
+        );
+        assert!(command_of(&["rust_epl", "players", "list", "--position", "keeper"]).is_err());
+        assert_eq!(
+            command_of(&[
+                "rust_epl",
+                "transfer",
+                "--out",
+                "1,2",
+                "--in",
+                "3,4",
+                "--wildcard"
+            ]),
+            Ok(Command::Transfer {
+                players_out: vec![1, 2],
+                players_in: vec![3, 4],
+                plan: None,
+                wildcard: true,
+                free_hit: false,
+                yes: false,
+            })
+        );
+        assert!(command_of(&["rust_epl", "transfer", "--out", "1", "--in", "3,4"]).is_err());
+        assert_eq!(
+            command_of(&["rust_epl", "transfer", "--plan", "plan.json"]),
+            Ok(Command::Transfer {
+                players_out: vec![],
+                players_in: vec![],
+                plan: Some(PathBuf::from("plan.json")),
+                wildcard: false,
+                free_hit: false,
+                yes: false,
+            })
+        );
+
+        assert_eq!(
+            command_of(&["rust_epl", "simulate", "--rival", "1234"]),
+            Ok(Command::Simulate { rival: Some(1234) })
+        );
+        assert_eq!(
+            command_of(&["rust_epl", "league", "314"]),
+            Ok(Command::League {
+                league_id: 314,
+                rivals: 50,
+                transfers: 5,
+            })
+        );
+        assert_eq!(
+            command_of(&[
+                "rust_epl",
+                "league",
+                "314",
+                "--rivals",
+                "10",
+                "--transfers",
+                "3"
+            ]),
+            Ok(Command::League {
+                league_id: 314,
+                rivals: 10,
+                transfers: 3,
+            })
+        );
+        assert!(command_of(&["rust_epl", "league", "classic"]).is_err());
+
+        // Options of the whole app go before the subcommand, a command's own after it
+        let yaml = load_yaml!("cli.yml");
+        let matches = App::from(yaml).get_matches_from([
+            "rust_epl",
+            "--profile",
+            "main",
+            "optimize",
+            "-u",
+            "1",
+            "-n",
+            "30",
+        ]);
+        let args = ArgChain::new(&matches);
+        assert_eq!(args.value_of("profile"), Some("main"));
+        let profile = Profile::from_matches(&args).unwrap();
+        assert_eq!(profile.user_id, Some(1));
+        assert_eq!(profile.top_n_players, Some(30));
+
+        // and each command only takes the options it uses
+        let yaml = load_yaml!("cli.yml");
+        let matches = App::from(yaml).get_matches_from([
+            "rust_epl",
+            "transfer",
+            "--out",
+            "1",
+            "--in",
+            "3",
+            "--free-transfers",
+            "0",
+            "--transfer-cost",
+            "2",
+            "-y",
+        ]);
+        let profile = Profile::from_matches(&ArgChain::new(&matches)).unwrap();
+        assert_eq!(profile.free_transfers, Some(0));
+        assert_eq!(profile.transfer_cost, Some(2.0));
+        assert!(matches!(
+            Command::from_matches(&matches, &ArgChain::new(&matches)),
+            Ok(Command::Transfer { yes: true, .. })
+        ));
+        let yaml = load_yaml!("cli.yml");
+        let matches = App::from(yaml).get_matches_from(["rust_epl", "league", "314", "-n", "40"]);
+        let profile = Profile::from_matches(&ArgChain::new(&matches)).unwrap();
+        assert_eq!(profile.top_n_players, Some(40));
+        for args in [
+            &["rust_epl", "snapshot", "gw13.json", "--objective", "risk"][..],
+            &["rust_epl", "-u", "1", "optimize"],
+            &[
+                "rust_epl",
+                "transfer",
+                "--out",
+                "1",
+                "--in",
+                "3",
+                "--exclude",
+                "5",
+            ],
+            &["rust_epl", "squad", "export", "squad.json", "-n", "30"],
+            &["rust_epl", "players", "list", "--risk-aversion", "2"],
+        ] {
+            let yaml = load_yaml!("cli.yml");
+            assert!(App::from(yaml).get_matches_from_safe(args).is_err());
+        }
+    }
+
+    #[test]
+    fn test_validation() {
+        let bad_env = |name: &str| match name {
+            "RUST_EPL_TRANSFER_COST" => Some(String::from("four")),
+            _ => None,
+        };
+        assert_eq!(
+            Profile::from_vars(bad_env).unwrap_err().to_string(),
+            "Error in configuration: invalid value `four` for RUST_EPL_TRANSFER_COST: invalid float literal"
+        );
+
+        let negative = Profile {
+            transfer_cost: Some(-4.0),
+            ..Profile::default()
+        };
+        assert!(Config::from_profile(Profile::defaults().merge(negative)).is_err());
+
+        let threshold = Profile {
+            min_player_metric: Some(2.5),
+            ..Profile::default()
+        };
+        let config = Config::from_profile(Profile::defaults().merge(threshold)).unwrap();
+        assert_eq!(config.top_n_player, None);
+        assert_eq!(config.min_player_metric, Some(2.5));
+        let not_a_number = Profile {
+            min_player_metric: Some(f32::NAN),
+            ..Profile::default()
+        };
+        assert!(Config::from_profile(Profile::defaults().merge(not_a_number)).is_err());
+        let both = Profile {
+            top_n_players: Some(30),
+            min_player_metric: Some(2.5),
+            ..Profile::default()
+        };
+        assert!(Config::from_profile(Profile::defaults().merge(both)).is_err());
+
+        let conflicting = Profile {
+            keep_players: Some(vec![3]),
+            exclude_players: Some(vec![3]),
+            ..Profile::default()
+        };
+        assert_eq!(
+            Config::from_profile(Profile::defaults().merge(conflicting)).unwrap_err(),
+            ConfigError(String::from("player 3 is both kept and excluded"))
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const EMAIL_ENV: &str = "FPL_EMAIL";
+const PASSWORD_ENV: &str = "FPL_PASSWORD";
+
+fn credentials_error(reason: &str) -> Box<dyn Error> {
+    Box::new(io::Error::other(format!(
+        "Error reading credentials: {}",
+        reason
+    )))
+}
+
+// Directory where the credentials and the saved session live, ~/.config/rust_epl
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".config").join("rust_epl"))
+}
+pub fn default_credentials_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("credentials"))
+}
+
+pub struct Credentials {
+    pub email: String,
+    pub password: String,
+}
+
+impl std::fmt::Debug for Credentials {
+    // Never print the password, even in debug output
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Credentials {{ email: {}, password: *** }}", self.email)
+    }
+}
+
+impl Credentials {
+    pub fn from_env() -> Option<Credentials> {
+        match (env::var(EMAIL_ENV), env::var(PASSWORD_ENV)) {
+            (Ok(email), Ok(password)) => Some(Credentials { email, password }),
+            _ => None,
+        }
+    }
+
+    // Reads a file with `email = ...` and `password = ...` lines. The file must
+    // only be accessible by its owner, the same rule ssh applies to private keys.
+    pub fn from_file(path: &Path) -> Result<Credentials, Box<dyn Error>> {
+        check_permissions(path)?;
+        Credentials::parse(&fs::read_to_string(path)?)
+            .map_err(|reason| credentials_error(&format!("{}: {}", path.display(), reason)))
+    }
+
+    fn parse(contents: &str) -> Result<Credentials, String> {
+        let mut email = None;
+        let mut password = None;
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or(format!("expected `key = value`, got `{}`", line))?;
+            let value = value.trim().trim_matches('"').to_string();
+            match key.trim() {
+                "email" => email = Some(value),
+                "password" => password = Some(value),
+                other => return Err(format!("unknown key `{}`", other)),
+            }
+        }
+        match (email, password) {
+            (Some(email), Some(password)) => Ok(Credentials { email, password }),
+            (None, _) => Err(String::from("missing email")),
+            (_, None) => Err(String::from("missing password")),
+        }
+    }
+
+    // Asks for the password without echoing it to the terminal
+    pub fn prompt(email: Option<&str>) -> Result<Credentials, Box<dyn Error>> {
+        let email = match email {
+            Some(email) => email.to_string(),
+            None => {
+                eprint!("Email: ");
+                io::stderr().flush()?;
+                let mut input_text = String::new();
+                io::stdin().read_line(&mut input_text)?;
+                input_text.trim().to_string()
+            }
+        };
+        let password = rpassword::prompt_password(format!("Password for {}: ", email))?;
+        Ok(Credentials { email, password })
+    }
+
+    // Interactive prompt when asked for, otherwise the environment, otherwise the
+    // credentials file (explicit path, or the default one if it exists).
+    pub fn resolve(
+        interactive: bool,
+        email: Option<&str>,
+        file: Option<&Path>,
+    ) -> Result<Credentials, Box<dyn Error>> {
+        if interactive {
+            return Credentials::prompt(email);
+        }
+        if let Some(credentials) = Credentials::from_env() {
+            return Ok(credentials);
+        }
+        if let Some(path) = file {
+            return Credentials::from_file(path);
+        }
+        match default_credentials_path() {
+            Some(path) if path.exists() => Credentials::from_file(&path),
+            _ => Err(credentials_error(&format!(
+                "set {} and {}, write a credentials file, or pass --password to be prompted",
+                EMAIL_ENV, PASSWORD_ENV
+            ))),
+        }
+    }
+}
+
+#[cfg(unix)]
+pub fn check_permissions(path: &Path) -> Result<(), Box<dyn Error>> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = fs::metadata(path)?.permissions().mode();
+    if mode & 0o077 != 0 {
+        return Err(credentials_error(&format!(
+            "{} is accessible by other users (mode {:o}), run `chmod 600 {}`",
+            path.display(),
+            mode & 0o777,
+            path.display()
+        )));
+    }
+    Ok(())
+}
+#[cfg(not(unix))]
+pub fn check_permissions(_path: &Path) -> Result<(), Box<dyn Error>> {
+    Ok(())
+}
+
+// Creates (or truncates) a file only readable by its owner
+pub fn create_private_file(path: &Path) -> io::Result<fs::File> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let credentials = Credentials::parse(
+            "# fantasy log in\nemail = someone@example.com\npassword = \"hunter2\"\n",
+        )
+        .unwrap();
+        assert_eq!(credentials.email, "someone@example.com");
+        assert_eq!(credentials.password, "hunter2");
+        assert_eq!(
+            format!("{:?}", credentials),
+            "Credentials { email: someone@example.com, password: *** }"
+        );
+
+        assert_eq!(
+            Credentials::parse("email = someone@example.com").unwrap_err(),
+            "missing password"
+        );
+        assert_eq!(
+            Credentials::parse("user = someone").unwrap_err(),
+            "unknown key `user`"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = env::temp_dir().join(format!("rust_epl_credentials_{}", std::process::id()));
+        create_private_file(&path)
+            .unwrap()
+            .write_all(b"email = a@b.com\npassword = pw\n")
+            .unwrap();
+        assert_eq!(Credentials::from_file(&path).unwrap().password, "pw");
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        let error = Credentials::from_file(&path).unwrap_err().to_string();
+        assert!(error.contains("accessible by other users"));
+        fs::remove_file(&path).unwrap();
+    }
+}
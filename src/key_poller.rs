@@ -26,7 +26,7 @@ impl KeyPoller {
     pub fn new(key: Keycode) -> Self {
         KeyPoller {
             was_pressed: false,
-            key: key,
+            key,
             device_state: DeviceState::new(),
         }
     }
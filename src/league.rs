@@ -0,0 +1,251 @@
+use crate::player::Player;
+use crate::simulation::Simulation;
+use crate::squad::Squad;
+use crate::transfer::Transfer;
+use std::collections::HashMap;
+
+pub const DEFAULT_RIVALS: usize = 50; // One page of standings
+pub const DEFAULT_TRANSFERS: usize = 5;
+
+// Average multiplier the league's managers picked each player with, by player id:
+// 1 when every manager starts them, 2 when every manager captains them. Players
+// nobody picked aren't in it.
+pub fn effective_ownership(picks: &[HashMap<u16, u8>]) -> HashMap<u16, f32> {
+    let mut ownership: HashMap<u16, f32> = HashMap::new();
+    for multipliers in picks {
+        for (id, multiplier) in multipliers {
+            *ownership.entry(*id).or_insert(0.0) += *multiplier as f32;
+        }
+    }
+    let managers = picks.len().max(1) as f32;
+    ownership.values_mut().for_each(|o| *o /= managers);
+    ownership
+}
+
+// Multipliers the squad's players score with in its best lineup, the captain's
+// including the armband
+pub fn squad_multipliers(squad: &Squad, captain_upside: f32) -> HashMap<u16, f32> {
+    let mut multipliers: HashMap<u16, f32> = squad.players.iter().map(|p| (p.id, 0.0)).collect();
+    for player in squad.best_starter_lineup().players {
+        multipliers.insert(player.id, 1.0);
+    }
+    if let Some(option) = squad.captaincy(captain_upside).first() {
+        multipliers.insert(option.captain.id, squad.rules().captain_multiplier);
+    }
+    multipliers
+}
+
+// How much more of a player's points we score than the average manager in the league
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exposure {
+    pub player: Player,
+    pub ours: f32,   // Our multiplier
+    pub league: f32, // The league's effective ownership
+}
+
+impl Exposure {
+    // Points we're expected to gain on the league through the player, negative
+    // when we're exposed to their points and positive when they're a differential
+    pub fn swing(&self) -> f32 {
+        self.player.metric() * (self.ours - self.league)
+    }
+}
+
+// Players we or the league own, biggest swing either way first
+pub fn exposures(
+    list: &[Player],
+    ours: &HashMap<u16, f32>,
+    league: &HashMap<u16, f32>,
+) -> Vec<Exposure> {
+    let mut exposures: Vec<Exposure> = list
+        .iter()
+        .filter(|p| ours.contains_key(&p.id) || league.contains_key(&p.id))
+        .map(|p| Exposure {
+            player: p.clone(),
+            ours: ours.get(&p.id).copied().unwrap_or(0.0),
+            league: league.get(&p.id).copied().unwrap_or(0.0),
+        })
+        .collect();
+    exposures.sort_by(|a, b| b.swing().abs().partial_cmp(&a.swing().abs()).unwrap());
+    exposures
+}
+
+// Our total and the rivals' totals with the points they score in each simulated
+// run of the gameweek
+pub struct LeagueRace {
+    pub total: i32,
+    pub rivals: Vec<(i32, Vec<f32>)>,
+}
+
+impl LeagueRace {
+    // Our average rank in the league after the gameweek when we score `points`
+    // in each run, rivals ending level counting as half a place above us
+    pub fn expected_rank(&self, points: &[f32]) -> f32 {
+        let runs = points.len().max(1) as f32;
+        let places_behind: f32 = self
+            .rivals
+            .iter()
+            .map(|(total, rival_points)| {
+                points
+                    .iter()
+                    .zip(rival_points)
+                    .map(|(ours, theirs)| {
+                        let ours = self.total as f32 + ours;
+                        let theirs = *total as f32 + theirs;
+                        match theirs.partial_cmp(&ours) {
+                            Some(std::cmp::Ordering::Greater) => 1.0,
+                            Some(std::cmp::Ordering::Equal) => 0.5,
+                            _ => 0.0,
+                        }
+                    })
+                    .sum::<f32>()
+            })
+            .sum();
+        1.0 + places_behind / runs
+    }
+}
+
+// A transfer and our expected rank in the league after making it
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankTransfer {
+    pub transfer: Transfer,
+    pub expected_rank: f32,
+}
+
+// The single transfers to `candidates` that most improve our expected rank on
+// what `squad` is expected to reach, best first and at most `n` of them. `hit`
+// is taken off the points of every transfer.
+pub fn best_transfers(
+    race: &LeagueRace,
+    squad: &Squad,
+    candidates: &[Player],
+    simulation: &mut Simulation,
+    captain_upside: f32,
+    hit: f32,
+    n: usize,
+) -> Vec<RankTransfer> {
+    let current = race.expected_rank(&simulation.points(squad, captain_upside));
+    let mut transfers = Vec::new();
+    for out in &squad.players {
+        for in_ in candidates
+            .iter()
+            .filter(|p| p.position == out.position && !squad.has_player(p))
+        {
+            let new_squad =
+                match squad.after_transfers(std::slice::from_ref(out), std::slice::from_ref(in_)) {
+                    Ok(new_squad) => new_squad,
+                    Err(_) => continue,
+                };
+            let points: Vec<f32> = simulation
+                .points(&new_squad, captain_upside)
+                .iter()
+                .map(|p| p - hit)
+                .collect();
+            let expected_rank = race.expected_rank(&points);
+            if expected_rank < current {
+                transfers.push(RankTransfer {
+                    transfer: Transfer {
+                        out: out.clone(),
+                        in_: in_.clone(),
+                    },
+                    expected_rank,
+                });
+            }
+        }
+    }
+    transfers.sort_by(|a, b| a.expected_rank.partial_cmp(&b.expected_rank).unwrap());
+    transfers.truncate(n);
+    transfers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::Position;
+    use crate::simulation::SimulationSettings;
+
+    // Players 1 to 15 worth 2 each but for midfielder 8 who's worth 6
+    fn squad() -> Squad {
+        Squad::with_metric(2.0, &[Player::with_chance(8, Position::MID, 3, 6.0, 1.0)])
+    }
+
+    #[test]
+    fn test_effective_ownership() {
+        let picks: Vec<HashMap<u16, u8>> = vec![
+            [(1, 2), (2, 1), (3, 0)].iter().copied().collect(),
+            [(1, 1), (4, 3)].iter().copied().collect(),
+        ];
+        let ownership = effective_ownership(&picks);
+        assert_eq!(ownership[&1], 1.5);
+        assert_eq!(ownership[&2], 0.5);
+        assert_eq!(ownership[&3], 0.0);
+        assert_eq!(ownership[&4], 1.5);
+        assert!(!ownership.contains_key(&5));
+        assert!(effective_ownership(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_exposures() {
+        let squad = squad();
+        let ours = squad_multipliers(&squad, 0.0);
+        assert_eq!(ours.len(), 15);
+        assert_eq!(ours.values().filter(|m| **m > 0.0).count(), 11);
+        assert_eq!(ours[&8], 2.0);
+
+        // The league captains a forward we don't have and barely owns our captain
+        let outsider = Player::with_chance(20, Position::FWD, 6, 8.0, 1.0);
+        let mut list = squad.players.clone();
+        list.push(outsider);
+        let league: HashMap<u16, f32> = [(20, 1.8), (8, 0.2)].iter().copied().collect();
+        let exposures = exposures(&list, &ours, &league);
+        assert_eq!(exposures.len(), 16);
+        assert_eq!(exposures[0].player.id, 20);
+        assert!((exposures[0].swing() + 14.4).abs() < 1e-4);
+        assert_eq!(exposures[1].player.id, 8);
+        assert!((exposures[1].swing() - 10.8).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_expected_rank() {
+        let race = LeagueRace {
+            total: 100,
+            rivals: vec![
+                (90, vec![5.0, 5.0, 5.0, 5.0]),    // Always behind
+                (110, vec![5.0, 5.0, 5.0, 5.0]),   // Always ahead
+                (100, vec![0.0, 10.0, 20.0, 5.0]), // Behind, level, ahead, ahead
+            ],
+        };
+        let points = [5.0, 10.0, 10.0, 0.0];
+        assert!((race.expected_rank(&points) - (1.0 + 1.0 + 2.5 / 4.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_best_transfers() {
+        let squad = squad();
+        let mut simulation = Simulation::new(SimulationSettings {
+            runs: 500,
+            seed: 3,
+            team_correlation: 0.0,
+        });
+        // A rival level on points who has our squad and a better forward
+        let star = Player::with_chance(20, Position::FWD, 6, 8.0, 1.0);
+        let rival = squad
+            .after_transfers(&squad.players[14..], std::slice::from_ref(&star))
+            .unwrap();
+        let race = LeagueRace {
+            total: 0,
+            rivals: vec![(0, simulation.points(&rival, 0.0))],
+        };
+        let weak = Player::with_chance(21, Position::FWD, 7, 1.0, 1.0);
+        let candidates = [star.clone(), weak];
+        let transfers = best_transfers(&race, &squad, &candidates, &mut simulation, 0.0, 0.0, 2);
+        assert!(!transfers.is_empty());
+        assert_eq!(transfers[0].transfer.in_, star);
+        assert_eq!(transfers[0].transfer.out.position, Position::FWD);
+        assert!(transfers.iter().all(|t| t.transfer.in_.id == 20));
+
+        // A hit too big to make up for leaves nothing worth doing
+        let hit = best_transfers(&race, &squad, &candidates, &mut simulation, 0.0, 50.0, 2);
+        assert!(hit.is_empty());
+    }
+}
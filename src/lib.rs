@@ -1,68 +1,69 @@
+use crate::api::Session;
+pub use crate::config::{Command, Config};
+use crate::credentials::Credentials;
+use crate::league::LeagueRace;
 use crate::logger::Logger;
+use crate::model::Model;
 use crate::optimizer::Listener;
 use crate::optimizer::Optimizer;
-use crate::player::Player;
+use crate::player::{Metric, Player};
+use crate::projections::Projections;
+use crate::query::PlayerQuery;
+use crate::report::{
+    player_table, BacktestReport, LeagueReport, OutputFormat, PlayerRow, Recommendation,
+    SearchStats, SimulationReport, SquadReport,
+};
+use crate::simulation::Simulation;
+use crate::source::{DataSource, FplApi, Snapshot};
 use crate::squad::Squad;
 use crate::top_squad::TopSquad;
-use clap::{load_yaml, App};
+use crate::transfer::TransferPlan;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
 use std::rc::Rc;
 
-use std::io;
-
-const CAPTAIN_MULTIPLIER: f32 = 2.0;
+use std::io::{self, Write};
 
 mod api;
+mod autosub;
+mod blend;
+mod captaincy;
+mod compact_squad;
+mod config;
+mod credentials;
 mod key_poller;
+mod league;
+mod lineup;
 mod logger;
+mod minutes;
+mod model;
+mod money;
 mod optimizer;
 mod player;
+mod projections;
+mod query;
+mod report;
+mod rules;
+mod simulation;
+mod source;
 mod squad;
+mod squad_file;
 mod team;
 mod top_squad;
+mod transfer;
 
 #[derive(Debug)]
 struct PlayerNotFound(String);
-fn add_by_last_name(
-    squad: &mut Squad,
-    last_name: String,
-    full_list: &Vec<Player>,
-) -> Result<(), PlayerNotFound> {
-    for player in full_list {
-        if player.name == last_name {
-            squad.try_add_player(&player).expect("Error adding player");
-            return Ok(());
-        }
-    }
-    Err(PlayerNotFound(format!(
-        "Couldn't find player: {}",
-        last_name
-    )))
-}
-
-fn custom_squad(full_list: &Vec<Player>) -> Squad {
-    let money_in_bank = 0.0;
-    let mut current_squad = Squad::new(1000.0);
-    add_by_last_name(&mut current_squad, "Martínez".to_string(), &full_list).unwrap();
-    add_by_last_name(&mut current_squad, "Sánchez".to_string(), &full_list).unwrap();
-    add_by_last_name(&mut current_squad, "Robertson".to_string(), &full_list).unwrap();
-    add_by_last_name(&mut current_squad, "Cresswell".to_string(), &full_list).unwrap();
-    add_by_last_name(&mut current_squad, "Wan-Bissaka".to_string(), &full_list).unwrap();
-    add_by_last_name(&mut current_squad, "Dunk".to_string(), &full_list).unwrap();
-    add_by_last_name(&mut current_squad, "Targett".to_string(), &full_list).unwrap();
-    add_by_last_name(&mut current_squad, "Fernandes".to_string(), &full_list).unwrap();
-    add_by_last_name(&mut current_squad, "Son".to_string(), &full_list).unwrap();
-    add_by_last_name(&mut current_squad, "Dallas".to_string(), &full_list).unwrap();
-    add_by_last_name(&mut current_squad, "Harrison".to_string(), &full_list).unwrap();
-    add_by_last_name(&mut current_squad, "Ward-Prowse".to_string(), &full_list).unwrap();
-    add_by_last_name(&mut current_squad, "Benteke".to_string(), &full_list).unwrap();
-    add_by_last_name(&mut current_squad, "Bamford".to_string(), &full_list).unwrap();
-    add_by_last_name(&mut current_squad, "Watkins".to_string(), &full_list).unwrap();
-    current_squad.sort_players();
-    current_squad.set_max_cost(money_in_bank + current_squad.total_cost());
-    current_squad
+impl fmt::Display for PlayerNotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
 }
+impl Error for PlayerNotFound {}
 
 fn get_top_n_players(full_list: Vec<Player>, n_players: usize, squad: &Squad) -> Vec<Player> {
     let mut result: Vec<Player> = Vec::with_capacity(n_players + squad.players.len());
@@ -84,6 +85,18 @@ fn get_top_n_players(full_list: Vec<Player>, n_players: usize, squad: &Squad) ->
     result
 }
 
+// The squad's players and every other player worth more than `min_metric`, best first
+fn get_players_above(full_list: Vec<Player>, min_metric: f32, squad: &Squad) -> Vec<Player> {
+    let mut result = squad.players.clone();
+    result.extend(
+        full_list
+            .into_iter()
+            .filter(|p| p.metric() > min_metric && !squad.has_player(p)),
+    );
+    sort_by_metric(&mut result);
+    result
+}
+
 // fn run(){
 // let mut poller = KeyPoller::new(Keycode::P);
 // loop {
@@ -94,7 +107,7 @@ fn get_top_n_players(full_list: Vec<Player>, n_players: usize, squad: &Squad) ->
 // }
 
 fn read_gameweek() -> u8 {
-    println!("What was the last gameweek?");
+    eprintln!("What was the last gameweek?");
     let mut input_text = String::new();
     io::stdin()
         .read_line(&mut input_text)
@@ -104,43 +117,667 @@ fn read_gameweek() -> u8 {
     trimmed.parse::<u8>().unwrap()
 }
 
+// Asks on stderr, anything but y or yes is a no
+fn confirm(question: &str) -> Result<bool, Box<dyn Error>> {
+    eprint!("{} [y/N] ", question);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+// Reuses browser or saved cookies while the server still accepts them, otherwise
+// logs in with a password and saves the new session
+fn open_session(config: &Config, gameweek: u8) -> Result<Session, Box<dyn Error>> {
+    let session = Session::new(config.user_id, gameweek)?;
+    let saved_session = config.session_file.as_ref().filter(|path| path.exists());
+    let cookies = match (&config.cookie_file, saved_session) {
+        (Some(path), _) => {
+            session.import_cookies(path)?;
+            Some(path)
+        }
+        (None, Some(path)) => {
+            session.load_cookies(path)?;
+            Some(path)
+        }
+        (None, None) => None,
+    };
+    if let Some(path) = cookies {
+        match session.validate() {
+            // Transfers would go to whichever team the cookies log in to
+            Ok(entry) if entry != config.user_id => {
+                return Err(format!(
+                    "Cookies in {} are for manager {}, not user-id {}",
+                    path.display(),
+                    entry,
+                    config.user_id
+                )
+                .into())
+            }
+            Ok(_) => return Ok(session),
+            Err(e) => eprintln!("{} ({}), trying to log in instead", e, path.display()),
+        }
+    }
+    let credentials = Credentials::resolve(
+        config.password,
+        config.email.as_deref(),
+        config.credentials_file.as_deref(),
+    )
+    .map_err(|e| match cookies {
+        Some(path) => format!(
+            "Cookies in {} have expired, export them again from the browser or provide a password. {}",
+            path.display(),
+            e
+        )
+        .into(),
+        None => e,
+    })?;
+    session.log_in(&credentials.email, &credentials.password)?;
+    if let Some(path) = &config.session_file {
+        session.save_cookies(path)?;
+    }
+    Ok(session)
+}
+
+fn sort_by_metric(list: &mut [Player]) {
+    list.sort_by(|a, b| {
+        b.metric()
+            .partial_cmp(&a.metric())
+            .expect("Error sorting players")
+    });
+}
+
+// The file given with --data-file, otherwise the live game
+fn data_source(config: &Config) -> Result<Box<dyn DataSource>, Box<dyn Error>> {
+    match &config.data_file {
+        Some(path) => source::from_file(path),
+        None => Ok(Box::new(FplApi::new(config.user_id))),
+    }
+}
+
+// --gameweek, then the gameweek the source says is under way, then asks
+fn current_gameweek(config: &Config, source: &dyn DataSource) -> u8 {
+    config
+        .gameweek
+        .or_else(|| source.current_gameweek())
+        .unwrap_or_else(read_gameweek)
+}
+
+fn load_players(config: &Config, source: &dyn DataSource) -> Result<Vec<Player>, Box<dyn Error>> {
+    let mut list = source.players()?;
+    if let Some(path) = &config.projections_file {
+        let projections = Projections::load(path, &list)?;
+        for row in &projections.unmatched {
+            eprintln!("Skipped projections in {}, {}", path.display(), row);
+        }
+        projections.apply(&mut list);
+    }
+    evaluate_players(config, source, &mut list)?;
+    sort_by_metric(&mut list);
+    Ok(list)
+}
+
+// Expected minutes, then the model's points and the metric that may depend on them
+fn evaluate_players(
+    config: &Config,
+    source: &dyn DataSource,
+    list: &mut [Player],
+) -> Result<(), Box<dyn Error>> {
+    let finished = source.gameweeks()?.iter().filter(|g| g.finished).count();
+    for player in list.iter_mut() {
+        player.expected_minutes = minutes::estimate(player, finished);
+        player.set_minutes_discount(config.minutes_risk);
+    }
+    if config.uses_metric(Metric::Model) {
+        let teams = source.teams()?;
+        let fixtures = source.fixtures()?;
+        Model::new(&teams, &fixtures, config.model_horizon).project(list);
+    }
+    match &config.blend {
+        Some(blend) => list.iter_mut().for_each(|p| p.set_blend(blend)),
+        None => list.iter_mut().for_each(|p| p.set_metric(config.metric)),
+    }
+    if config.exclude_unavailable {
+        list.iter_mut()
+            .filter(|p| p.status.is_out())
+            .for_each(Player::rule_out);
+    }
+    Ok(())
+}
+
+// The squad to work from, empty when the squad file starts a fresh one
+fn load_squad(
+    config: &Config,
+    source: &dyn DataSource,
+    list: &[Player],
+) -> Result<Squad, Box<dyn Error>> {
+    if let Some(path) = &config.squad_file {
+        return squad_file::load_squad(path, list, config.rules);
+    }
+    let gameweek = current_gameweek(config, source);
+    if config.log_in {
+        open_session(config, gameweek)?.get_my_squad(list)
+    } else {
+        source.current_squad(gameweek, list)
+    }
+}
+
+fn load_current_squad(
+    config: &Config,
+    source: &dyn DataSource,
+    list: &[Player],
+) -> Result<Squad, Box<dyn Error>> {
+    let squad = load_squad(config, source, list)?;
+    if squad.players.is_empty() {
+        return Err("The squad file has no players, only optimize can pick a fresh squad".into());
+    }
+    Ok(squad)
+}
+
+fn find_players(ids: &[u16], list: &[Player]) -> Result<Vec<Player>, PlayerNotFound> {
+    ids.iter()
+        .map(|id| {
+            list.iter()
+                .find(|p| p.id == *id)
+                .cloned()
+                .ok_or_else(|| PlayerNotFound(format!("Couldn't find player with id: {}", id)))
+        })
+        .collect()
+}
+
 pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let list = api::get_full_sorted_player_list().unwrap();
-    let current_squad = if config.overwrite_pulled_team {
-        custom_squad(&list)
+    match &config.command {
+        Command::Optimize { save_plan } => optimize(&config, save_plan.as_deref()),
+        Command::SquadShow => show_squad(&config),
+        Command::SquadExport { file } => {
+            let source = data_source(&config)?;
+            let list = load_players(&config, source.as_ref())?;
+            let squad = load_current_squad(&config, source.as_ref(), &list)?;
+            fs::write(file, serde_json::to_string_pretty(&squad)?)?;
+            println!("Saved squad to {}", file.display());
+            Ok(())
+        }
+        Command::Players(query) => list_players(&config, query),
+        Command::PlayersExport { file } => {
+            let list = load_players(&config, data_source(&config)?.as_ref())?;
+            if file.extension().is_some_and(|e| e == "json") {
+                fs::write(file, serde_json::to_string_pretty(&list)?)?;
+            } else {
+                player::write_csv(&list, fs::File::create(file)?)?;
+            }
+            println!("Saved {} players to {}", list.len(), file.display());
+            Ok(())
+        }
+        Command::Transfer {
+            players_out,
+            players_in,
+            plan: Some(plan),
+            wildcard,
+            free_hit,
+            yes,
+        } if players_out.is_empty() && players_in.is_empty() => {
+            let plan = TransferPlan::load(plan)?;
+            transfer(
+                &config,
+                &plan.players_out(),
+                &plan.players_in(),
+                *wildcard || plan.wildcard,
+                *free_hit || plan.free_hit,
+                *yes,
+            )
+        }
+        Command::Transfer {
+            players_out,
+            players_in,
+            wildcard,
+            free_hit,
+            yes,
+            ..
+        } => transfer(&config, players_out, players_in, *wildcard, *free_hit, *yes),
+        Command::Lineup => show_lineup(&config),
+        Command::Simulate { rival } => simulate(&config, *rival),
+        Command::League {
+            league_id,
+            rivals,
+            transfers,
+        } => league(&config, *league_id, *rivals, *transfers),
+        Command::Backtest { snapshot } => backtest(&config, snapshot),
+        Command::Snapshot { file } => {
+            api::save_snapshot(file)?;
+            println!("Saved snapshot to {}", file.display());
+            Ok(())
+        }
+    }
+}
+
+fn show_squad(config: &Config) -> Result<(), Box<dyn Error>> {
+    let source = data_source(config)?;
+    let list = load_players(config, source.as_ref())?;
+    let squad = load_current_squad(config, source.as_ref(), &list)?;
+    let report = SquadReport::new(&squad);
+    match config.output {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::Text => {
+            println!("Squad cost: {:.1}, bank: {:.1}", report.cost, report.bank);
+            for player in squad.organized_players() {
+                println!("  {}", player);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn list_players(config: &Config, query: &PlayerQuery) -> Result<(), Box<dyn Error>> {
+    let source = data_source(config)?;
+    let list = load_players(config, source.as_ref())?;
+    let difficulty = if query.needs_fixtures() {
+        source.fixture_difficulty(query.horizon)?
     } else {
-        let gameweek = config.gameweek.unwrap_or_else(||read_gameweek());
-        api::get_my_squad(config.user_id, gameweek, &list)?
+        HashMap::new()
     };
-    let reduced_list = if let Some(_) = config.min_player_metric {
-        panic!("Min_acceptable player metric not implemented yet");
+    let rows: Vec<PlayerRow> = query
+        .run(&list, &difficulty)
+        .into_iter()
+        .map(|p| PlayerRow::new(p, difficulty.get(&p.team.id()).cloned()))
+        .collect();
+    match config.output {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+        OutputFormat::Text => print!("{}", player_table(&rows)),
+    }
+    Ok(())
+}
+
+fn transfer(
+    config: &Config,
+    players_out: &[u16],
+    players_in: &[u16],
+    wildcard: bool,
+    free_hit: bool,
+    yes: bool,
+) -> Result<(), Box<dyn Error>> {
+    let source = data_source(config)?;
+    let list = load_players(config, source.as_ref())?;
+    let players_out = find_players(players_out, &list)?;
+    let players_in = find_players(players_in, &list)?;
+    let gameweek = current_gameweek(config, source.as_ref());
+    let session = open_session(config, gameweek)?;
+    let current_squad = session.get_my_squad(&list)?;
+    if let Some(player) = players_out.iter().find(|p| !current_squad.has_player(p)) {
+        return Err(Box::new(PlayerNotFound(format!(
+            "{} is not in the squad",
+            player.name
+        ))));
+    }
+    // Paired by position from the resulting squad, whatever order --out and --in were given in
+    let new_squad = current_squad
+        .after_transfers(&players_out, &players_in)
+        .map_err(|e| format!("Can't make these transfers: {}", e))?;
+    let transfers = new_squad.transfers_from(&current_squad);
+    // The wildcard and the free hit make every transfer free
+    let free_transfers = if wildcard || free_hit {
+        transfers.len()
     } else {
-        get_top_n_players(
+        config.free_transfers
+    };
+    // With JSON the outcome goes to stderr, so stdout is only the squad being submitted
+    let notice = |message: &str| match config.output {
+        OutputFormat::Json => eprintln!("{}", message),
+        OutputFormat::Text => println!("{}", message),
+    };
+    match config.output {
+        OutputFormat::Json => {
+            let recommendation = Recommendation::new(
+                &new_squad,
+                &current_squad,
+                free_transfers,
+                config.rules.hit_cost,
+                config.captain_upside,
+                SearchStats::default(),
+            );
+            println!("{}", recommendation.to_json()?);
+        }
+        OutputFormat::Text => {
+            print!("{}", new_squad.changes_from(&current_squad));
+            println!(
+                "Bank change: {:+.1}, hits: {}",
+                transfer::bank_delta(&transfers),
+                transfer::hits(&transfers, free_transfers)
+            );
+        }
+    }
+    // Submitted transfers can't be taken back and hits cost points
+    if !yes && !confirm("Submit these transfers?")? {
+        notice("Transfers not submitted");
+        return Ok(());
+    }
+    session.transfer(&transfers, wildcard, free_hit)?;
+    notice("Transfers submitted");
+    Ok(())
+}
+
+// Recent minutes take a request per player, so only the squad's are fetched
+fn with_recent_minutes(
+    config: &Config,
+    source: &dyn DataSource,
+    squad: &Squad,
+) -> Result<Squad, Box<dyn Error>> {
+    let mut players = squad.players.clone();
+    for player in players.iter_mut() {
+        player.recent_minutes = source.recent_minutes(player.id)?;
+    }
+    evaluate_players(config, source, &mut players)?;
+    Ok(squad_file::refresh_squad(squad, &players)?)
+}
+
+fn show_lineup(config: &Config) -> Result<(), Box<dyn Error>> {
+    let source = data_source(config)?;
+    let list = load_players(config, source.as_ref())?;
+    let squad = load_current_squad(config, source.as_ref(), &list)?;
+    let squad = with_recent_minutes(config, source.as_ref(), &squad)?;
+    match config.output {
+        OutputFormat::Json => {
+            let recommendation = Recommendation::new(
+                &squad,
+                &squad,
+                config.free_transfers,
+                config.rules.hit_cost,
+                config.captain_upside,
+                SearchStats::default(),
+            );
+            println!("{}", recommendation.to_json()?);
+        }
+        OutputFormat::Text => println!("{}", squad.lineup_description(config.captain_upside)),
+    }
+    Ok(())
+}
+
+// Simulates the current squad's gameweek, and the rival's squad in the same runs
+fn simulate(config: &Config, rival: Option<u32>) -> Result<(), Box<dyn Error>> {
+    let source = data_source(config)?;
+    let list = load_players(config, source.as_ref())?;
+    let squad = load_current_squad(config, source.as_ref(), &list)?;
+    let squad = with_recent_minutes(config, source.as_ref(), &squad)?;
+    let mut simulation = Simulation::new(config.simulation);
+    let points = simulation.points(&squad, config.captain_upside);
+    let rival_points = match rival {
+        Some(user_id) => {
+            let gameweek = current_gameweek(config, source.as_ref());
+            let rival_squad = FplApi::new(user_id).current_squad(gameweek, &list)?;
+            let rival_squad = with_recent_minutes(config, source.as_ref(), &rival_squad)?;
+            Some((
+                user_id,
+                simulation.points(&rival_squad, config.captain_upside),
+            ))
+        }
+        None => None,
+    };
+    let report = SimulationReport::new(
+        &config.simulation,
+        &points,
+        rival_points
+            .as_ref()
+            .map(|(user_id, points)| (*user_id, points.as_slice())),
+    );
+    match config.output {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::Text => {
+            println!(
+                "Simulated {} gameweeks: mean {:.1} points, stdev {:.1}",
+                report.runs, report.mean, report.stdev
+            );
+            let percentiles: Vec<String> = report
+                .percentiles
+                .iter()
+                .map(|(p, points)| format!("{}%: {}", p, points))
+                .collect();
+            println!("  Percentiles: {}", percentiles.join(", "));
+            if let Some(rival) = &report.rival {
+                println!(
+                    "  Rival {}: mean {:.1} points, stdev {:.1}, beaten in {:.1}% of the gameweeks",
+                    rival.user_id,
+                    rival.mean,
+                    rival.stdev,
+                    100.0 * rival.chance_to_beat
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+// Simulates the squad against the managers at the top of the league, who play
+// their current squads, and weighs our lineup against the players they picked
+fn league(
+    config: &Config,
+    league_id: u32,
+    rivals: usize,
+    n_transfers: usize,
+) -> Result<(), Box<dyn Error>> {
+    let source = data_source(config)?;
+    let list = load_players(config, source.as_ref())?;
+    let squad = load_current_squad(config, source.as_ref(), &list)?;
+    let gameweek = current_gameweek(config, source.as_ref());
+    let (name, standings) = api::get_league_standings(league_id, rivals)?;
+    let total = match standings.iter().find(|e| e.entry == config.user_id) {
+        Some(entry) => entry.total,
+        None => api::get_entry_picks(config.user_id, gameweek, &list)?.total_points,
+    };
+    let mut simulation = Simulation::new(config.simulation);
+    let mut race = LeagueRace {
+        total,
+        rivals: Vec::new(),
+    };
+    let mut picks = Vec::new();
+    for entry in standings.iter().filter(|e| e.entry != config.user_id) {
+        let rival = api::get_entry_picks(entry.entry, gameweek, &list)?;
+        race.rivals.push((
+            entry.total,
+            simulation.points(&rival.squad, config.captain_upside),
+        ));
+        picks.push(rival.multipliers);
+    }
+    let exposures = league::exposures(
+        &list,
+        &league::squad_multipliers(&squad, config.captain_upside),
+        &league::effective_ownership(&picks),
+    );
+    let expected_rank = race.expected_rank(&simulation.points(&squad, config.captain_upside));
+    let candidates: Vec<Player> = list
+        .iter()
+        .filter(|p| !config.exclude_players.contains(&p.id))
+        .filter(|p| config.min_player_metric.is_none_or(|m| p.metric() > m))
+        .take(config.top_n_player.unwrap_or(list.len()))
+        .cloned()
+        .collect();
+    let hit = match config.free_transfers {
+        0 => config.rules.hit_cost,
+        _ => 0.0,
+    };
+    let transfers = league::best_transfers(
+        &race,
+        &squad,
+        &candidates,
+        &mut simulation,
+        config.captain_upside,
+        hit,
+        n_transfers,
+    );
+    let report = LeagueReport::new(
+        league_id,
+        name,
+        standings,
+        expected_rank,
+        &exposures,
+        &transfers,
+    );
+    match config.output {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::Text => {
+            println!("{}", report.name);
+            for entry in &report.standings {
+                println!(
+                    "  {:>3}. {} ({}) {}",
+                    entry.rank, entry.entry_name, entry.player_name, entry.total
+                );
+            }
+            println!(
+                "Expected rank after gameweek {}: {:.1}",
+                gameweek, report.expected_rank
+            );
+            for (title, players) in [
+                ("Exposed to", &report.exposed),
+                ("Differentials", &report.differentials),
+            ] {
+                let described: Vec<String> = players
+                    .iter()
+                    .map(|o| {
+                        format!(
+                            "{} ({:.2} vs {:.2}, {:+.1})",
+                            o.player.name, o.multiplier, o.effective_ownership, o.swing
+                        )
+                    })
+                    .collect();
+                println!("{}: {}", title, described.join(", "));
+            }
+            match report.transfers.as_slice() {
+                [] => println!("No transfer improves the expected rank"),
+                transfers => {
+                    println!("Transfers by expected rank:");
+                    for t in transfers {
+                        println!("  {} -> {}: {:.1}", t.out.name, t.in_.name, t.expected_rank);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Picks the lineup from the metrics in a snapshot taken before the gameweek and
+// scores it with the points that were actually scored. Without --gameweek that is
+// the snapshot's next gameweek.
+fn backtest(config: &Config, snapshot: &Path) -> Result<(), Box<dyn Error>> {
+    let source = Snapshot::load(snapshot)?;
+    let list = load_players(config, &source)?;
+    let gameweek = config
+        .gameweek
+        .or_else(|| {
+            source
+                .gameweeks()
+                .ok()?
+                .iter()
+                .find(|g| g.is_next)
+                .map(|g| g.id)
+        })
+        .unwrap_or_else(read_gameweek);
+    let squad = FplApi::new(config.user_id).current_squad(gameweek, &list)?;
+    if !squad.positions_full() {
+        return Err(format!(
+            "{} is missing players picked in gameweek {}",
+            snapshot.display(),
+            gameweek
+        )
+        .into());
+    }
+    let live = api::get_live_points(gameweek)?;
+    let report = BacktestReport::new(
+        gameweek,
+        &squad,
+        &live,
+        api::get_gameweek_points(config.user_id, gameweek)?,
+        config.captain_upside,
+    );
+    match config.output {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        OutputFormat::Text => println!(
+            "Gameweek {}: the lineup picked from {} would have scored {} points (predicted {:.2}), the manager scored {}",
+            gameweek,
+            snapshot.display(),
+            report.points,
+            report.predicted,
+            report.actual_points
+        ),
+    }
+    Ok(())
+}
+
+fn optimize(config: &Config, save_plan: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let source = data_source(config)?;
+    let mut list = load_players(config, source.as_ref())?;
+    let current_squad = load_squad(config, source.as_ref(), &list)?;
+    // Every player of a fresh squad is a free pick
+    let config = &Config {
+        free_transfers: match current_squad.players.len() {
+            0 => config.rules.squad_len(),
+            _ => config.free_transfers,
+        },
+        ..config.clone()
+    };
+    let excluded = |p: &Player| {
+        config.exclude_players.contains(&p.id) || (config.exclude_unavailable && p.status.is_out())
+    };
+    list.retain(|p| !excluded(p) || current_squad.has_player(p));
+    if config.output == OutputFormat::Text {
+        for player in current_squad.players.iter().filter(|p| p.status.is_out()) {
+            match player.news.as_str() {
+                "" => println!("{} is {}", player.name, player.status),
+                news => println!("{} is {} ({})", player.name, player.status, news),
+            }
+        }
+    }
+    // Kept players come from the whole list, whether or not they make the cut below
+    let mut new_squad = Squad::with_rules(current_squad.max_cost(), config.rules);
+    for id in &config.keep_players {
+        let kept = list
+            .iter()
+            .find(|p| p.id == *id)
+            .ok_or_else(|| PlayerNotFound(format!("Couldn't find player to keep: {}", id)))?;
+        new_squad
+            .try_add_player(kept)
+            .map_err(|e| format!("Can't keep player {}: {:?}", kept.name, e))?;
+    }
+    let mut reduced_list = match config.min_player_metric {
+        Some(min_metric) => get_players_above(list, min_metric, &current_squad),
+        None => get_top_n_players(
             list,
             config
                 .top_n_player
-                .expect("expected either a top_n_players value, or a min_acceptable_metric"),
+                .expect("expected either a top_n_players value, or a min_player_metric"),
             &current_squad,
-        )
+        ),
     };
+    reduced_list.retain(|p| !new_squad.has_player(p));
 
-    let logger = Rc::new(RefCell::new(Logger::new(&reduced_list)));
+    let logger = Rc::new(RefCell::new(Logger::new()));
     let top_squad_holder = Rc::new(RefCell::new(TopSquad::new(
         current_squad.clone(),
         config.clone(),
     )));
-    let mut new_squad = Squad::new(current_squad.max_cost());
     let mut optimizer = Optimizer::new(
         Some(current_squad.clone()),
-        config.transfer_cost,
-        None,
+        config.rules,
         Some(config.free_transfers),
         None,
         None,
     );
-    optimizer.register(Rc::clone(&logger) as Rc<RefCell<dyn Listener>>);
+    if config.output == OutputFormat::Text {
+        optimizer.register(Rc::clone(&logger) as Rc<RefCell<dyn Listener>>);
+    }
     optimizer.register(Rc::clone(&top_squad_holder) as Rc<RefCell<dyn Listener>>);
-    let _ = optimizer.fill_squad(&mut new_squad, &reduced_list);
+    let _ = optimizer.fill_squad(&new_squad, &reduced_list);
+    if let Some(path) = save_plan {
+        TransferPlan::new(&current_squad, top_squad_holder.borrow().top_squad()).save(path)?;
+        if config.output == OutputFormat::Text {
+            println!("Saved the transfers to {}", path.display());
+        }
+    }
+    if config.output == OutputFormat::Json {
+        let recommendation = top_squad_holder
+            .borrow_mut()
+            .recommendation(reduced_list.len() + config.keep_players.len());
+        println!("{}", recommendation.to_json()?);
+        return Ok(());
+    }
     println!(
         "Top Squad:\n{}",
         top_squad_holder.borrow().changes_for_top()
@@ -155,64 +792,3 @@ pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
     );
     Ok(())
 }
-
-#[derive(Debug, Clone)]
-pub struct Config {
-    pub gameweek: Option<u8>, // Not used yet
-    pub password: bool,       // Not used yet
-    pub user_id: u32,         // Not used yet
-    pub verbose: bool,        // Not used yet
-    pub top_n_player: Option<usize>,
-    pub free_transfers: usize,
-    pub overwrite_pulled_team: bool,
-    pub min_player_metric: Option<f32>,
-    pub transfer_cost: f32,
-    pub bench_point_value: f32, // Not used yet
-}
-impl Config {
-    pub fn parse_cli() -> Config {
-        let yaml = load_yaml!("cli.yml");
-        let m = App::from(yaml).get_matches();
-        let mut top_n_players = Some(
-            m.value_of("top_n_players")
-                .unwrap()
-                .parse::<usize>()
-                .unwrap(),
-        );
-        let min_metric = match m.value_of("min_metric") {
-            Some(metric) => {
-                top_n_players = None;
-                println!(
-                    "Choosing from players with metric > {} instead of using a top_n_players",
-                    metric.parse::<f32>().unwrap()
-                );
-                Some(metric.parse::<f32>().unwrap())
-            }
-            None => None,
-        };
-        let gameweek = match m.value_of("gameweek") {
-            Some(gweek) => Some(gweek.parse::<u8>().unwrap()),
-            None => None
-        };
-        Config {
-            gameweek: gameweek,
-            password: m.is_present("password"),
-            verbose: m.is_present("verbose"),
-            overwrite_pulled_team: m.is_present("overwrite"),
-            user_id: m.value_of("user_id").unwrap().parse::<u32>().unwrap(),
-            top_n_player: top_n_players,
-            free_transfers: m
-                .value_of("free_transfers")
-                .unwrap()
-                .parse::<usize>()
-                .unwrap(),
-            min_player_metric: min_metric,
-            transfer_cost: m.value_of("transfer_cost").unwrap().parse::<f32>().unwrap(),
-            bench_point_value: m
-                .value_of("bench_point_value")
-                .unwrap()
-                .parse::<f32>()
-                .unwrap(),
-        }
-    }
-}
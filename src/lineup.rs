@@ -0,0 +1,277 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+pub const N_POSITIONS: usize = 4;
+
+// How many players start and how many of them each position (GK, DEF, MID, FWD)
+// can have. Every lineup meeting these is a valid formation.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FormationRules {
+    pub lineup_size: usize,
+    pub min: [usize; N_POSITIONS],
+    pub max: [usize; N_POSITIONS],
+}
+
+// One goalkeeper, 3 to 5 defenders, 2 to 5 midfielders and 1 to 3 forwards
+impl Default for FormationRules {
+    fn default() -> Self {
+        FormationRules {
+            lineup_size: 11,
+            min: [1, 3, 2, 1],
+            max: [1, 5, 5, 3],
+        }
+    }
+}
+
+impl FormationRules {
+    #[cfg(test)]
+    pub fn new(
+        lineup_size: usize,
+        min: [usize; N_POSITIONS],
+        max: [usize; N_POSITIONS],
+    ) -> Result<Self, String> {
+        let rules = FormationRules {
+            lineup_size,
+            min,
+            max,
+        };
+        rules.validate()?;
+        Ok(rules)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self
+            .min
+            .iter()
+            .zip(self.max.iter())
+            .any(|(min, max)| min > max)
+        {
+            return Err(format!(
+                "a position has a higher minimum than maximum in {:?} to {:?}",
+                self.min, self.max
+            ));
+        }
+        if self.formations().is_empty() {
+            return Err(format!(
+                "no lineup of {} players has {:?} to {:?} players per position",
+                self.lineup_size, self.min, self.max
+            ));
+        }
+        Ok(())
+    }
+
+    // The rules for a squad with `capacities` places per position, whose lineups
+    // can't start more players than it has
+    pub fn for_capacities(&self, capacities: [usize; N_POSITIONS]) -> FormationRules {
+        let mut rules = *self;
+        for (position, capacity) in capacities.iter().enumerate() {
+            rules.min[position] = rules.min[position].min(*capacity);
+            rules.max[position] = rules.max[position].min(*capacity);
+        }
+        rules.lineup_size = rules.lineup_size.min(rules.max.iter().sum());
+        rules
+    }
+
+    pub fn allows(&self, starters: &[usize; N_POSITIONS]) -> bool {
+        starters.iter().sum::<usize>() == self.lineup_size
+            && (0..N_POSITIONS).all(|p| (self.min[p]..=self.max[p]).contains(&starters[p]))
+    }
+
+    // Every valid formation, as the number of starters of each position
+    pub fn formations(&self) -> Vec<[usize; N_POSITIONS]> {
+        let mut formations = Vec::new();
+        let mut starters = self.min;
+        loop {
+            if self.allows(&starters) {
+                formations.push(starters);
+            }
+            // Next combination, counting up from the last position
+            let mut position = N_POSITIONS;
+            loop {
+                if position == 0 {
+                    return formations;
+                }
+                position -= 1;
+                if starters[position] < self.max[position] {
+                    starters[position] += 1;
+                    break;
+                }
+                starters[position] = self.min[position];
+            }
+        }
+    }
+}
+
+// How many of each position start and the metric of those starters, the captain
+// counting `captain_multiplier` times
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lineup {
+    pub starters: [usize; N_POSITIONS],
+    pub metric: f32,
+}
+
+// The best lineup under `rules` from the metrics of each position sorted highest
+// first. The positions' best players start up to their minimum, then every place
+// left goes to the best player of a position that still has room, which is as
+// good as trying every formation. The captain is the best starter, and that is
+// the best player of the squad whenever they can start at all.
+pub fn best_lineup(
+    metrics: [&[f32]; N_POSITIONS],
+    rules: &FormationRules,
+    captain_multiplier: f32,
+) -> Lineup {
+    let mut starters = [0; N_POSITIONS];
+    for (position, n) in starters.iter_mut().enumerate() {
+        *n = rules.min[position].min(metrics[position].len());
+    }
+    for _ in starters.iter().sum::<usize>()..rules.lineup_size {
+        let mut next: Option<usize> = None;
+        for position in 0..N_POSITIONS {
+            let n = starters[position];
+            if n == rules.max[position] || n == metrics[position].len() {
+                continue;
+            }
+            let better = next.is_none_or(|best| {
+                metrics[position][n].partial_cmp(&metrics[best][starters[best]])
+                    == Some(Ordering::Greater)
+            });
+            if better {
+                next = Some(position);
+            }
+        }
+        match next {
+            Some(position) => starters[position] += 1,
+            None => break,
+        }
+    }
+
+    let metric: f32 = metrics
+        .iter()
+        .zip(starters.iter())
+        .flat_map(|(m, n)| m[..*n].iter())
+        .sum();
+    let captain = metrics
+        .iter()
+        .zip(starters.iter())
+        .filter(|(_, n)| **n > 0)
+        .map(|(m, _)| &m[0])
+        .fold(None, |best: Option<f32>, &m| {
+            Some(best.map_or(m, |b| b.max(m)))
+        });
+    Lineup {
+        starters,
+        metric: metric + captain.map_or(0.0, |c| (captain_multiplier - 1.0) * c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tries every formation
+    fn brute_force(
+        metrics: [&[f32]; N_POSITIONS],
+        rules: &FormationRules,
+        captain_multiplier: f32,
+    ) -> f32 {
+        rules
+            .formations()
+            .iter()
+            .map(|formation| {
+                let starters: Vec<f32> = metrics
+                    .iter()
+                    .zip(formation.iter())
+                    .flat_map(|(m, n)| m[..*n].iter().cloned())
+                    .collect();
+                let captain = starters.iter().cloned().fold(0.0, f32::max);
+                starters.iter().sum::<f32>() + (captain_multiplier - 1.0) * captain
+            })
+            .fold(f32::MIN, f32::max)
+    }
+
+    #[test]
+    fn test_formations() {
+        let mut formations = FormationRules::default().formations();
+        formations.sort();
+        assert_eq!(
+            formations,
+            vec![
+                [1, 3, 4, 3],
+                [1, 3, 5, 2],
+                [1, 4, 3, 3],
+                [1, 4, 4, 2],
+                [1, 4, 5, 1],
+                [1, 5, 2, 3],
+                [1, 5, 3, 2],
+                [1, 5, 4, 1],
+            ]
+        );
+
+        // A squad with one goalkeeper and one defender starts both of them
+        let small = FormationRules::default().for_capacities([1, 1, 0, 0]);
+        assert_eq!(small.formations(), vec![[1, 1, 0, 0]]);
+        assert!(small.validate().is_ok());
+
+        assert!(FormationRules::new(11, [1, 3, 2, 1], [1, 5, 5, 3]).is_ok());
+        assert!(FormationRules::new(12, [1, 3, 2, 1], [1, 3, 5, 2]).is_err());
+        assert!(FormationRules::new(5, [1, 3, 2, 1], [1, 5, 5, 3]).is_err());
+        assert!(FormationRules::new(11, [2, 3, 2, 1], [1, 5, 5, 3]).is_err());
+    }
+
+    #[test]
+    fn test_best_lineup() {
+        let rules = FormationRules::default();
+        let gk = [5.0, 1.0];
+        let def = [6.0, 4.0, 3.0, 2.5, 2.0];
+        let mid = [9.0, 7.0, 3.5, 3.0, 1.0];
+        let fwd = [8.0, 2.8, 0.5];
+        let lineup = best_lineup([&gk, &def, &mid, &fwd], &rules, 2.0);
+        assert_eq!(lineup.starters, [1, 4, 4, 2]);
+        assert!((lineup.metric - (5.0 + 15.5 + 22.5 + 10.8 + 9.0)).abs() < 1e-4);
+
+        // Squads that are still being filled start whoever they have
+        let lineup = best_lineup([&gk[..1], &[], &mid, &[]], &rules, 1.0);
+        assert_eq!(lineup.starters, [1, 0, 5, 0]);
+
+        // The best player can't captain when their position can't start
+        let no_forwards = FormationRules::new(7, [1, 2, 2, 0], [1, 4, 4, 0]).unwrap();
+        let lineup = best_lineup([&gk, &def, &mid[1..], &fwd], &no_forwards, 2.0);
+        assert_eq!(lineup.starters, [1, 3, 3, 0]);
+        assert!((lineup.metric - (5.0 + 13.0 + 13.5 + 7.0)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        // Pseudo random metrics, with whole numbers to get ties across positions
+        let mut seed: u32 = 7;
+        let mut next = || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            ((seed >> 16) % 40) as f32 / 4.0
+        };
+        let leagues = [
+            (FormationRules::default(), [2, 5, 5, 3]),
+            (FormationRules::default(), [1, 4, 4, 2]),
+            (
+                FormationRules::new(9, [1, 2, 0, 0], [2, 4, 4, 3]).unwrap(),
+                [3, 5, 5, 3],
+            ),
+        ];
+        for (rules, capacities) in leagues.iter() {
+            let rules = rules.for_capacities(*capacities);
+            for _ in 0..300 {
+                let mut squad: Vec<Vec<f32>> = capacities
+                    .iter()
+                    .map(|n| (0..*n).map(|_| next()).collect())
+                    .collect();
+                squad
+                    .iter_mut()
+                    .for_each(|m| m.sort_by(|a, b| b.partial_cmp(a).unwrap()));
+                let metrics = [&squad[0][..], &squad[1][..], &squad[2][..], &squad[3][..]];
+                let lineup = best_lineup(metrics, &rules, 2.0);
+                assert!(rules.allows(&lineup.starters));
+                assert!((lineup.metric - brute_force(metrics, &rules, 2.0)).abs() < 1e-4);
+            }
+        }
+    }
+}
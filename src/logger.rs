@@ -1,39 +1,30 @@
+use crate::compact_squad::{CompactSquad, PlayerPool};
 use crate::optimizer::Listener;
-use crate::Player;
-use crate::Squad;
 
 pub struct Logger {
     n_squads: usize,
     last_line: String,
-    full_player_list: Vec<Player>,
 }
 
 impl Logger {
-    pub fn new(full_player_list: &[Player]) -> Self {
+    pub fn new() -> Self {
         Logger {
             n_squads: 0,
             last_line: String::from(""),
-            full_player_list: full_player_list.to_vec(),
         }
     }
 }
 impl Listener for Logger {
-    fn notify_new_squad(&mut self, squad: &Squad) {
+    fn notify_new_squad(&mut self, pool: &PlayerPool, squad: &CompactSquad) {
         self.n_squads += 1;
-        if self.n_squads % 50 == 0 {
-            let mut line = String::from(format!(
+        if self.n_squads.is_multiple_of(50) {
+            let mut line = format!(
                 "Valid squads found: {}, Progress (over {}): ",
                 self.n_squads,
-                self.full_player_list.len()
-            ));
-            for player in &squad.players {
-                line.push_str(&format!(
-                    "{:?}, ",
-                    self.full_player_list
-                        .iter()
-                        .position(|p| p == player)
-                        .unwrap()
-                ));
+                pool.len()
+            );
+            for i in squad.indices() {
+                line.push_str(&format!("{:?}, ", i));
             }
             line.push_str(&format!("{: <1$}\r", "", 30));
             print!("\r{}", line);
@@ -1,8 +1,11 @@
-use std::process;
 use rust_epl::Config;
+use std::process;
 
 fn main() {
-    let config = Config::parse_cli();
+    let config = Config::parse_cli().unwrap_or_else(|e| {
+        println!("{}", e);
+        process::exit(1);
+    });
     // println!("{:?}", config);
 
     if let Err(e) = rust_epl::run(config) {
@@ -10,4 +13,3 @@ fn main() {
         process::exit(1);
     }
 }
-
@@ -0,0 +1,120 @@
+use crate::player::Player;
+use serde::Serialize;
+
+const RECENT_MATCHES: usize = 5; // Matches the recent minutes are taken from
+const FULL_MATCH: f32 = 90.0;
+const CAMEO: f32 = 30.0; // Minutes a substitute appearance is counted as
+const RISKY_START: f32 = 0.75; // Starters less likely than this to play 60 minutes are flagged
+
+// How long a player should play in their team's next match
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ExpectedMinutes {
+    pub minutes: f32,
+    pub start: f32,   // Chance of playing at least 60 minutes
+    pub appears: f32, // Chance of playing at all
+}
+
+impl ExpectedMinutes {
+    // Share of a full match
+    pub fn share(&self) -> f32 {
+        self.minutes / FULL_MATCH
+    }
+    pub fn risky(&self) -> bool {
+        self.start < RISKY_START
+    }
+}
+
+// From the minutes of the player's last matches when known, otherwise their
+// minutes and starts per gameweek this season, then scaled by their chance of
+// playing. None before the first gameweek is finished and with no recent matches.
+pub fn estimate(player: &Player, finished_gameweeks: usize) -> Option<ExpectedMinutes> {
+    let recent =
+        &player.recent_minutes[player.recent_minutes.len().saturating_sub(RECENT_MATCHES)..];
+    let (minutes, start, appears) = if !recent.is_empty() {
+        let n = recent.len() as f32;
+        let share = |f: fn(&u32) -> bool| recent.iter().filter(|m| f(m)).count() as f32 / n;
+        (
+            recent
+                .iter()
+                .map(|m| (*m as f32).min(FULL_MATCH))
+                .sum::<f32>()
+                / n,
+            share(|m| *m >= 60),
+            share(|m| *m > 0),
+        )
+    } else if finished_gameweeks > 0 {
+        let gameweeks = finished_gameweeks as f32;
+        let minutes = (player.stats.minutes as f32 / gameweeks).min(FULL_MATCH);
+        let start = (player.stats.starts as f32 / gameweeks).min(1.0);
+        (minutes, start, (minutes / CAMEO).min(1.0).max(start))
+    } else {
+        return None;
+    };
+    Some(ExpectedMinutes {
+        minutes: minutes * player.health,
+        start: start * player.health,
+        appears: appears * player.health,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+    use crate::player::{Position, SeasonStats};
+    use crate::team::Team;
+
+    fn player(minutes: u32, starts: u32, health: f32) -> Player {
+        let mut player = Player::new(
+            0.0,
+            health,
+            Money::from_tenths(50),
+            String::from("Player"),
+            Position::MID,
+            1,
+            Team::new(1),
+            0,
+            0.0,
+        );
+        player.stats = SeasonStats {
+            minutes,
+            starts,
+            ..SeasonStats::default()
+        };
+        player
+    }
+
+    #[test]
+    fn test_season() {
+        let regular = estimate(&player(900, 10, 1.0), 10).unwrap();
+        assert_eq!(regular.minutes, 90.0);
+        assert_eq!((regular.start, regular.appears), (1.0, 1.0));
+        assert!(!regular.risky());
+
+        // Starts half the games and comes on in some of the others
+        let rotated = estimate(&player(540, 5, 1.0), 10).unwrap();
+        assert_eq!(rotated.share(), 0.6);
+        assert_eq!((rotated.start, rotated.appears), (0.5, 1.0));
+        assert!(rotated.risky());
+
+        let doubtful = estimate(&player(900, 10, 0.5), 10).unwrap();
+        assert_eq!(doubtful.minutes, 45.0);
+        assert!(doubtful.risky());
+
+        let substitute = estimate(&player(150, 0, 1.0), 10).unwrap();
+        assert_eq!((substitute.start, substitute.appears), (0.0, 0.5));
+        assert_eq!(estimate(&player(0, 0, 1.0), 0), None);
+    }
+
+    #[test]
+    fn test_recent() {
+        // Lost their place lately, only the last five matches count
+        let mut dropped = player(1800, 20, 1.0);
+        dropped.recent_minutes = vec![90, 90, 90, 90, 0, 15, 90, 0, 0];
+        let minutes = estimate(&dropped, 20).unwrap();
+        assert_eq!(minutes.minutes, 21.0);
+        assert_eq!((minutes.start, minutes.appears), (0.2, 0.4));
+        assert!(minutes.risky());
+        assert!(estimate(&dropped, 0).is_some());
+    }
+}
@@ -0,0 +1,324 @@
+use crate::player::{Player, Position};
+use crate::source::{Fixture, TeamInfo};
+use std::collections::{BTreeMap, HashMap};
+
+const LEAGUE_GOALS: f32 = 1.4; // Goals an average team scores in a match
+const PRIOR_MINUTES: f32 = 450.0; // How much the position's average rates count for every player
+const MAX_GOALS: u32 = 15; // Goals conceded worth adding up in a match
+const ASSIST_POINTS: f32 = 3.0;
+const AVERAGE_DIFFICULTY: f32 = 3.0;
+
+// Points for a goal, a clean sheet and every two goals conceded
+fn scoring(position: &Position) -> (f32, f32, f32) {
+    match position {
+        Position::GK => (10.0, 4.0, -1.0),
+        Position::DEF => (6.0, 4.0, -1.0),
+        Position::MID => (5.0, 1.0, 0.0),
+        Position::FWD => (4.0, 0.0, 0.0),
+    }
+}
+
+// Goals, assists and bonus points per 90 minutes of an average player of the position
+fn prior_rates(position: &Position) -> (f32, f32, f32) {
+    match position {
+        Position::GK => (0.0, 0.01, 0.15),
+        Position::DEF => (0.05, 0.07, 0.25),
+        Position::MID => (0.15, 0.15, 0.3),
+        Position::FWD => (0.35, 0.15, 0.4),
+    }
+}
+
+// Per 90 minutes, pulled towards the position's average while a player has few minutes
+fn per_90(total: u32, minutes: u32, prior: f32) -> f32 {
+    (total as f32 + prior * PRIOR_MINUTES / 90.0) / ((minutes as f32 + PRIOR_MINUTES) / 90.0)
+}
+
+// Expected value of floor(goals / 2) when the goals follow a Poisson distribution
+fn expected_pairs(mean: f32) -> f32 {
+    let mut probability = (-mean).exp();
+    let mut pairs = 0.0;
+    for goals in 1..=MAX_GOALS {
+        probability *= mean / goals as f32;
+        pairs += (goals / 2) as f32 * probability;
+    }
+    pairs
+}
+
+// One team's side of a fixture
+struct Match {
+    gameweek: u8,
+    team: u8,
+    opponent: u8,
+    home: bool,
+    difficulty: u8,
+}
+
+// Projects the points of the players of every team in the next `horizon` gameweeks
+// from what they did so far this season, how long they should play and who each
+// team plays
+pub struct Model<'a> {
+    teams: HashMap<u8, &'a TeamInfo>,
+    matches: Vec<Match>,
+    gameweeks: Vec<u8>,
+    average_attack: f32,
+    average_defence: f32,
+}
+
+impl<'a> Model<'a> {
+    pub fn new(teams: &'a [TeamInfo], fixtures: &[Fixture], horizon: usize) -> Self {
+        let mut gameweeks: Vec<u8> = fixtures.iter().filter_map(|f| f.event).collect();
+        gameweeks.sort_unstable();
+        gameweeks.dedup();
+        gameweeks.truncate(horizon);
+        let matches = fixtures
+            .iter()
+            .filter(|f| f.event.is_some_and(|e| gameweeks.contains(&e)))
+            .flat_map(|f| {
+                let gameweek = f.event.unwrap_or_default();
+                vec![
+                    Match {
+                        gameweek,
+                        team: f.team_h,
+                        opponent: f.team_a,
+                        home: true,
+                        difficulty: f.team_h_difficulty,
+                    },
+                    Match {
+                        gameweek,
+                        team: f.team_a,
+                        opponent: f.team_h,
+                        home: false,
+                        difficulty: f.team_a_difficulty,
+                    },
+                ]
+            })
+            .collect();
+        let average = |strengths: Vec<u32>| {
+            let known: Vec<f32> = strengths
+                .into_iter()
+                .filter(|s| *s > 0)
+                .map(|s| s as f32)
+                .collect();
+            known.iter().sum::<f32>() / known.len().max(1) as f32
+        };
+        Model {
+            average_attack: average(
+                teams
+                    .iter()
+                    .flat_map(|t| [t.strength_attack_home, t.strength_attack_away])
+                    .collect(),
+            ),
+            average_defence: average(
+                teams
+                    .iter()
+                    .flat_map(|t| [t.strength_defence_home, t.strength_defence_away])
+                    .collect(),
+            ),
+            teams: teams.iter().map(|t| (t.id, t)).collect(),
+            matches,
+            gameweeks,
+        }
+    }
+
+    // Goals the team is expected to score and concede, from the attack and defence
+    // strengths at the ground when both teams have them, otherwise from the difficulty
+    fn goals(&self, m: &Match) -> (f32, f32) {
+        let strengths = |id: u8, home: bool| {
+            self.teams.get(&id).map(|t| {
+                if home {
+                    (t.strength_attack_home, t.strength_defence_home)
+                } else {
+                    (t.strength_attack_away, t.strength_defence_away)
+                }
+            })
+        };
+        match (strengths(m.team, m.home), strengths(m.opponent, !m.home)) {
+            (Some((attack, defence)), Some((opponent_attack, opponent_defence)))
+                if attack > 0 && defence > 0 && opponent_attack > 0 && opponent_defence > 0 =>
+            {
+                let rating = |attack: u32, defence: u32| {
+                    LEAGUE_GOALS * (attack as f32 / self.average_attack)
+                        / (defence as f32 / self.average_defence)
+                };
+                (
+                    rating(attack, opponent_defence),
+                    rating(opponent_attack, defence),
+                )
+            }
+            _ => {
+                let difficulty = m.difficulty.max(1) as f32 / AVERAGE_DIFFICULTY;
+                (LEAGUE_GOALS / difficulty, LEAGUE_GOALS * difficulty)
+            }
+        }
+    }
+
+    // Expected points in each modelled gameweek, 0 when the team doesn't play or
+    // the player's minutes aren't known
+    pub fn expected_points(&self, player: &Player) -> BTreeMap<u8, f32> {
+        let mut points: BTreeMap<u8, f32> = self.gameweeks.iter().map(|gw| (*gw, 0.0)).collect();
+        let (minutes, start, appears) = match player.expected_minutes {
+            Some(m) if m.minutes > 0.0 => (m.minutes, m.start, m.appears),
+            _ => return points,
+        };
+        let stats = &player.stats;
+        let (goal, clean_sheet, conceded) = scoring(&player.position);
+        let (prior_goals, prior_assists, prior_bonus) = prior_rates(&player.position);
+        let goals = per_90(stats.goals_scored, stats.minutes, prior_goals) * minutes / 90.0;
+        let assists = per_90(stats.assists, stats.minutes, prior_assists) * minutes / 90.0;
+        let bonus = per_90(stats.bonus, stats.minutes, prior_bonus) * minutes / 90.0;
+        // 2 points for 60 minutes, 1 for less
+        let appearance = 2.0 * start + (appears - start);
+
+        for m in self.matches.iter().filter(|m| m.team == player.team.id()) {
+            let (scored, against) = self.goals(m);
+            let attack = scored / LEAGUE_GOALS;
+            *points.entry(m.gameweek).or_default() += appearance
+                + goals * attack * goal
+                + assists * attack * ASSIST_POINTS
+                + bonus
+                + start * (-against).exp() * clean_sheet
+                + start * expected_pairs(against) * conceded;
+        }
+        points
+    }
+
+    pub fn project(&self, players: &mut [Player]) {
+        for player in players.iter_mut() {
+            player.model_points = self.expected_points(player);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::minutes;
+    use crate::money::Money;
+    use crate::player::SeasonStats;
+    use crate::team::Team;
+
+    fn team(id: u8, attack: u32, defence: u32) -> TeamInfo {
+        TeamInfo {
+            id,
+            strength_attack_home: attack,
+            strength_attack_away: attack,
+            strength_defence_home: defence,
+            strength_defence_away: defence,
+            ..TeamInfo::default()
+        }
+    }
+
+    fn fixture(gameweek: u8, home: u8, away: u8, difficulty: (u8, u8)) -> Fixture {
+        Fixture {
+            event: Some(gameweek),
+            team_h: home,
+            team_a: away,
+            team_h_difficulty: difficulty.0,
+            team_a_difficulty: difficulty.1,
+        }
+    }
+
+    // Playing time from the stats as if `gameweeks` are finished
+    fn player(position: Position, team: u8, stats: SeasonStats, gameweeks: usize) -> Player {
+        let mut player = Player::new(
+            0.0,
+            1.0,
+            Money::from_tenths(50),
+            String::from("Player"),
+            position,
+            1,
+            Team::new(team),
+            0,
+            0.0,
+        );
+        player.stats = stats;
+        player.expected_minutes = minutes::estimate(&player, gameweeks);
+        player
+    }
+
+    // Ten full games with 5 goals, 2 assists and 6 bonus points
+    const STRIKER: SeasonStats = SeasonStats {
+        minutes: 900,
+        starts: 10,
+        goals_scored: 5,
+        assists: 2,
+        bonus: 6,
+    };
+
+    #[test]
+    fn test_expected_pairs() {
+        assert_eq!(expected_pairs(0.0), 0.0);
+        // P(2 or 3) + 2 P(4 or 5) + ... for a mean of 1
+        let e = (-1.0f32).exp();
+        let by_hand: f32 = (2..=MAX_GOALS)
+            .map(|g| (g / 2) as f32 * e / (1..=g).map(|k| k as f32).product::<f32>())
+            .sum();
+        assert!((expected_pairs(1.0) - by_hand).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_by_difficulty() {
+        // Teams without strengths fall back to the fixture difficulty
+        let teams = vec![team(1, 0, 0), team(2, 0, 0)];
+        let fixtures = vec![fixture(11, 1, 2, (3, 3)), fixture(12, 2, 1, (2, 5))];
+        let model = Model::new(&teams, &fixtures, 1);
+        let points = model.expected_points(&player(Position::FWD, 1, STRIKER, 10));
+        assert_eq!(points.keys().cloned().collect::<Vec<_>>(), vec![11]);
+        // 2 for the start, 6.75 goals and 2.75 assists and 8 bonus over 15 games
+        let by_hand = 2.0 + 0.45 * 4.0 + 2.75 / 15.0 * 3.0 + 8.0 / 15.0;
+        assert!((points[&11] - by_hand).abs() < 1e-4);
+
+        let model = Model::new(&teams, &fixtures, 2);
+        let points = model.expected_points(&player(Position::FWD, 1, STRIKER, 10));
+        assert!(points[&12] < points[&11]);
+    }
+
+    #[test]
+    fn test_by_strength() {
+        let teams = vec![
+            team(1, 1300, 1300),
+            team(2, 1100, 1100),
+            team(3, 1200, 1200),
+        ];
+        let fixtures = vec![
+            fixture(5, 1, 2, (2, 4)),
+            fixture(6, 3, 1, (3, 3)),
+            fixture(6, 1, 2, (2, 4)),
+            fixture(7, 2, 3, (3, 3)),
+            fixture(8, 1, 3, (3, 3)),
+        ];
+        let model = Model::new(&teams, &fixtures, 3);
+        let defender = SeasonStats {
+            minutes: 360,
+            starts: 4,
+            ..SeasonStats::default()
+        };
+        let strong = model.expected_points(&player(Position::DEF, 1, defender, 4));
+        let weak = model.expected_points(&player(Position::DEF, 2, defender, 4));
+        // A double and a blank gameweek for team 1, nothing past the horizon
+        assert_eq!(strong.keys().cloned().collect::<Vec<_>>(), vec![5, 6, 7]);
+        assert_eq!(strong[&7], 0.0);
+        assert!(strong[&6] > 1.5 * strong[&5]);
+        assert_eq!(weak[&6], weak[&5]);
+        // Clean sheets are likelier behind the stronger defence
+        assert!(strong[&5] > weak[&5] + 0.5);
+
+        // Players who haven't played, or can't, get nothing
+        let benched = model.expected_points(&player(Position::DEF, 1, SeasonStats::default(), 4));
+        assert!(benched.values().all(|p| *p == 0.0));
+        let mut injured = player(Position::FWD, 1, STRIKER, 4);
+        injured.health = 0.0;
+        injured.expected_minutes = minutes::estimate(&injured, 4);
+        assert!(model.expected_points(&injured).values().all(|p| *p == 0.0));
+        let mut doubtful = player(Position::FWD, 1, STRIKER, 4);
+        doubtful.health = 0.5;
+        doubtful.expected_minutes = minutes::estimate(&doubtful, 4);
+        let full = model.expected_points(&player(Position::FWD, 1, STRIKER, 4));
+        assert!((model.expected_points(&doubtful)[&5] - full[&5] / 2.0).abs() < 1e-4);
+
+        // The same data gives the same points
+        let mut players = vec![player(Position::MID, 3, STRIKER, 4)];
+        model.project(&mut players);
+        assert_eq!(players[0].model_points, model.expected_points(&players[0]));
+    }
+}
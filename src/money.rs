@@ -0,0 +1,133 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+// An amount in tenths of a million, the unit the fantasy server counts prices in,
+// so budgets add up exactly. Shown and serialized in millions, e.g. 5.5.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Money(i32);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+    // Budget of a squad that isn't limited by money
+    pub const UNLIMITED: Money = Money(i32::MAX);
+
+    pub const fn from_tenths(tenths: i32) -> Money {
+        Money(tenths)
+    }
+    pub fn from_millions(millions: f64) -> Money {
+        Money((millions * 10.0).round() as i32)
+    }
+    pub fn tenths(self) -> i32 {
+        self.0
+    }
+    pub fn millions(self) -> f32 {
+        self.0 as f32 / 10.0
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+    fn add(self, other: Money) -> Money {
+        Money(self.0.saturating_add(other.0))
+    }
+}
+impl Sub for Money {
+    type Output = Money;
+    fn sub(self, other: Money) -> Money {
+        Money(self.0.saturating_sub(other.0))
+    }
+}
+impl AddAssign for Money {
+    fn add_assign(&mut self, other: Money) {
+        *self = *self + other;
+    }
+}
+impl SubAssign for Money {
+    fn sub_assign(&mut self, other: Money) {
+        *self = *self - other;
+    }
+}
+impl Neg for Money {
+    type Output = Money;
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        iter.fold(Money::ZERO, Add::add)
+    }
+}
+impl<'a> Sum<&'a Money> for Money {
+    fn sum<I: Iterator<Item = &'a Money>>(iter: I) -> Money {
+        iter.copied().sum()
+    }
+}
+
+impl fmt::Display for Money {
+    // Millions with one decimal unless a precision is given, honouring width and `+`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precision = f.precision().unwrap_or(1);
+        let digits = format!("{:.*}", precision, (self.0 as f64 / 10.0).abs());
+        f.pad_integral(self.0 >= 0, "", &digits)
+    }
+}
+
+impl FromStr for Money {
+    type Err = String;
+    // Millions, like the prices shown by the game
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().parse::<f64>() {
+            Ok(millions) if millions.is_finite() => Ok(Money::from_millions(millions)),
+            _ => Err(format!(
+                "expected an amount in millions like 5.5, got `{}`",
+                s
+            )),
+        }
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.0 as f64 / 10.0)
+    }
+}
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        f64::deserialize(deserializer).map(Money::from_millions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_money() {
+        let price = Money::from_tenths(55);
+        assert_eq!(price.millions(), 5.5);
+        assert_eq!("5.5".parse::<Money>(), Ok(price));
+        // Decimal millions that aren't exact in binary still land on the right tenth
+        assert_eq!(Money::from_millions(0.1 + 0.2), Money::from_tenths(3));
+        assert!("five".parse::<Money>().is_err());
+
+        let total: Money = [55, 45, 100].iter().map(|t| Money::from_tenths(*t)).sum();
+        assert_eq!(total, Money::from_tenths(200));
+        assert_eq!(total - price, Money::from_tenths(145));
+        assert!(Money::UNLIMITED > total + price);
+
+        assert_eq!(price.to_string(), "5.5");
+        assert_eq!(format!("{:.2}", price), "5.50");
+        assert_eq!(format!("{:>6}", price), "   5.5");
+        assert_eq!(format!("{:+}", price), "+5.5");
+        assert_eq!(format!("{:+}", -price), "-5.5");
+        assert_eq!((-price).to_string(), "-5.5");
+
+        assert_eq!(serde_json::to_string(&price).unwrap(), "5.5");
+        assert_eq!(serde_json::from_str::<Money>("5.5").unwrap(), price);
+        assert_eq!(serde_json::from_str::<Money>("100").unwrap().tenths(), 1000);
+    }
+}
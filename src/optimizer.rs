@@ -1,20 +1,23 @@
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::compact_squad::{CompactSquad, PlayerPool};
+use crate::money::Money;
+use crate::rules::GameRules;
 use crate::Player;
 use crate::Squad;
+use std::cell::RefCell;
 use std::f32;
+use std::rc::Rc;
 
 pub trait Listener {
-    fn notify_new_squad(&mut self, squad: &Squad);
+    fn notify_new_squad(&mut self, pool: &PlayerPool, squad: &CompactSquad);
 }
 #[derive(Debug, PartialEq)]
 pub struct SquadNotFull(String);
 
 pub struct Optimizer {
-    transfer_cost: f32,
+    rules: GameRules,
     squad_max_len: usize,
     observers: Vec<Rc<RefCell<dyn Listener>>>,
-    cheapest_cost: Option<f32>,
+    cheapest_cost: Option<Money>,
     current_squad: Option<Squad>,
     n_free_transfers: usize,
     min_metric: Option<f32>,
@@ -25,33 +28,31 @@ pub struct Optimizer {
 impl Optimizer {
     pub fn new(
         current_squad: Option<Squad>,
-        transfer_cost: f32,
-        squad_max_len: Option<usize>,
+        rules: GameRules,
         n_free_transfers: Option<usize>,
         min_metric: Option<f32>,
         max_metric: Option<f32>,
     ) -> Self {
-        let squad_max_len = squad_max_len.unwrap_or(15);
+        let squad_max_len = rules.squad_len();
         let n_free_transfers = n_free_transfers.unwrap_or(squad_max_len);
         Optimizer {
-            transfer_cost: transfer_cost,
-            squad_max_len: squad_max_len,
+            rules,
+            squad_max_len,
             observers: Vec::new(),
             cheapest_cost: None,
-            current_squad: current_squad,
-            n_free_transfers: n_free_transfers,
-            min_metric: min_metric,
-            max_metric: max_metric,
+            current_squad,
+            n_free_transfers,
+            min_metric,
+            max_metric,
             stack_i: 1,
         }
     }
-    pub fn register(&mut self, logger: Rc<RefCell<dyn Listener>>)
-    {
+    pub fn register(&mut self, logger: Rc<RefCell<dyn Listener>>) {
         self.observers.push(logger);
     }
-    pub fn trigger_callbacks(&mut self, squad: &Squad) {
+    pub fn trigger_callbacks(&mut self, pool: &PlayerPool, squad: &CompactSquad) {
         for logger in &self.observers {
-            logger.borrow_mut().notify_new_squad(&squad);
+            logger.borrow_mut().notify_new_squad(pool, squad);
         }
     }
 
@@ -78,37 +79,41 @@ impl Optimizer {
             available_players
                 .iter()
                 .map(|p| p.price)
-                .fold(f32::INFINITY, |a, b| a.min(b)),
+                .min()
+                .unwrap_or(Money::ZERO),
         );
     }
 
     fn skip_step(
         &self,
         no_new_players: bool,
-        squad: &Squad,
-        len_players: usize,
-        p: &Player,
+        pool: &PlayerPool,
+        squad: &CompactSquad,
+        i: usize,
     ) -> bool {
-        if len_players < self.squad_max_len - 1
-            && (squad.max_cost() - (squad.total_cost() + p.price))
-                / ((self.squad_max_len - 1 - len_players) as f32)
-                <= self.cheapest_cost.expect("Cheapest cost is not defined")
+        let price = pool.price(i);
+        // Money left after player `i` must buy at least the cheapest player for every place still open
+        let places_left = self.squad_max_len.saturating_sub(squad.len() + 1) as i32;
+        if places_left > 0
+            && (squad.max_cost() - (squad.total_cost() + price)).tenths()
+                < self
+                    .cheapest_cost
+                    .expect("Cheapest cost is not defined")
+                    .tenths()
+                    * places_left
         {
             return true;
         }
-        if squad.total_cost() + p.price > squad.max_cost() {
+        if squad.total_cost() + price > squad.max_cost() {
             return true;
         }
-        if let Some(c_squad) = &self.current_squad {
-            if no_new_players && !c_squad.has_player(p) {
-                return true;
-            }
-        }
-        false
+        self.current_squad.is_some() && no_new_players && !pool.in_current(i)
     }
+    // Calls the listeners with every valid squad made of `squad` and players of
+    // `available_players`. The search runs on a `CompactSquad` over a pool of both.
     pub fn fill_squad(
         &mut self,
-        squad: &mut Squad,
+        squad: &Squad,
         available_players: &[Player],
     ) -> Result<(), SquadNotFull> {
         if self.min_metric.is_none() {
@@ -120,42 +125,125 @@ impl Optimizer {
         if self.cheapest_cost.is_none() {
             self.update_cheapest_cost(available_players);
         }
+        let pool = PlayerPool::new(
+            squad
+                .players
+                .iter()
+                .chain(available_players)
+                .cloned()
+                .collect(),
+            self.current_squad.as_ref(),
+        );
+        let mut compact = CompactSquad::new(&pool, squad);
+        for i in 0..squad.players.len() {
+            compact.add(&pool, i);
+        }
+        self.search(&pool, &mut compact, squad.players.len())
+    }
+
+    // Tries every pool player from `start` on in the next place of `squad`
+    fn search(
+        &mut self,
+        pool: &PlayerPool,
+        squad: &mut CompactSquad,
+        start: usize,
+    ) -> Result<(), SquadNotFull> {
         let mut no_new_players = false;
-        if let Some(current_squad) = &self.current_squad {
-            let changes_so_far = squad.number_of_changes(&current_squad);
+        if self.current_squad.is_some() {
+            let changes_so_far = squad.number_of_changes();
             no_new_players = changes_so_far > self.n_free_transfers
                 && self.max_metric.expect("Error: metric not set")
                     - self.min_metric.expect("Error: metric not set")
-                    < self.transfer_cost;
+                    < self.rules.hit_cost;
         }
         let no_new_players = no_new_players; // Remove mutability
 
-        let len_players = squad.players.len();
-        if available_players.len() == 0
-            || len_players + available_players.len() < self.squad_max_len
-        {
+        let n_available = pool.len() - start;
+        if n_available == 0 || squad.len() + n_available < self.squad_max_len {
             return Err(SquadNotFull("Not enough Players".to_string()));
         }
 
-        for (i, p) in available_players.iter().enumerate() {
-            assert!(!squad.has_player(p));
-            if self.skip_step(no_new_players, &squad, len_players, &p) {
+        for i in start..pool.len() {
+            assert!(!squad.contains(i));
+            if self.skip_step(no_new_players, pool, squad, i) {
                 continue;
             }
-            if let Err(_) = squad.try_add_player(&p) {
+            if !squad.fits(pool, i) {
                 continue;
             }
+            squad.add(pool, i);
 
             if squad.positions_full() {
                 // Valid squad found
-                self.trigger_callbacks(squad);
-            } else if let Some(next_player) = available_players.get(i + 1) {
-                self.max_metric = Some(next_player.metric());
+                self.trigger_callbacks(pool, squad);
+            } else if i + 1 < pool.len() {
+                self.max_metric = Some(pool.metric(i + 1));
                 self.stack_i += 1;
-                let _ = self.fill_squad(squad, &available_players[i + 1..]);
+                let _ = self.search(pool, squad, i + 1);
             }
-            squad.remove_player(&p);
+            squad.pop(pool);
         }
         Err(SquadNotFull(String::from("Squad not full")))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::Position;
+    use crate::team::Team;
+
+    #[derive(Default)]
+    struct Collector {
+        squads: Vec<Vec<u16>>,
+    }
+    impl Listener for Collector {
+        fn notify_new_squad(&mut self, pool: &PlayerPool, squad: &CompactSquad) {
+            let mut ids: Vec<u16> = squad.indices().iter().map(|&i| pool.player(i).id).collect();
+            ids.sort();
+            self.squads.push(ids);
+        }
+    }
+
+    #[test]
+    fn test_fill_squad() {
+        let positions = [
+            (Position::GK, 3),
+            (Position::DEF, 5),
+            (Position::MID, 5),
+            (Position::FWD, 3),
+        ];
+        let mut players = Vec::new();
+        for (position, n) in positions.iter() {
+            for _ in 0..*n {
+                let id = players.len() as u16 + 1;
+                players.push(Player::new(
+                    0.0,
+                    1.0,
+                    Money::from_tenths(50),
+                    format!("Player{}", id),
+                    position.clone(),
+                    id,
+                    Team::new(id as u8),
+                    0,
+                    20.0 - id as f32,
+                ));
+            }
+        }
+        // Player 1 is kept, so the only choice left is the second goalkeeper
+        let mut squad = Squad::new(Money::from_tenths(750));
+        squad.try_add_player(&players[0]).unwrap();
+        let collector = Rc::new(RefCell::new(Collector::default()));
+        let mut optimizer = Optimizer::new(None, GameRules::fpl_classic(), None, None, None);
+        optimizer.register(Rc::clone(&collector) as Rc<RefCell<dyn Listener>>);
+        let _ = optimizer.fill_squad(&squad, &players[1..]);
+
+        let squads = &collector.borrow().squads;
+        assert_eq!(squads.len(), 2);
+        assert!(squads[0].contains(&2) && !squads[0].contains(&3));
+        assert!(squads[1].contains(&3) && !squads[1].contains(&2));
+        assert!(squads.iter().all(|s| s.len() == 15 && s.contains(&1)));
+        // The kept player is still the only one in the squad passed in
+        assert_eq!(squad.players.len(), 1);
+    }
+}
@@ -1,7 +1,16 @@
+use crate::blend::Blend;
+use crate::minutes::ExpectedMinutes;
+use crate::money::Money;
 use crate::team::Team;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
 use std::fmt;
+use std::io;
+use std::str::FromStr;
 
-#[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Position {
     GK,
     DEF,
@@ -9,6 +18,18 @@ pub enum Position {
     FWD,
 }
 
+impl Position {
+    // Index into per-position arrays, goalkeepers first
+    pub fn index(&self) -> usize {
+        match self {
+            Position::GK => 0,
+            Position::DEF => 1,
+            Position::MID => 2,
+            Position::FWD => 3,
+        }
+    }
+}
+
 impl fmt::Display for Position {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -20,17 +41,152 @@ impl fmt::Display for Position {
     }
 }
 
-#[derive(Clone)]
+impl FromStr for Position {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "GK" | "GKP" => Ok(Position::GK),
+            "DEF" => Ok(Position::DEF),
+            "MID" => Ok(Position::MID),
+            "FWD" => Ok(Position::FWD),
+            _ => Err(format!(
+                "unknown position `{}`, expected one of: GK, DEF, MID, FWD",
+                s
+            )),
+        }
+    }
+}
+
+// Availability flag the game gives every player, by its one letter code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Status {
+    #[serde(rename = "d")]
+    Doubtful,
+    #[serde(rename = "i")]
+    Injured,
+    #[serde(rename = "s")]
+    Suspended,
+    #[serde(rename = "u")]
+    Unavailable, // Left the club or loaned out of the league
+    #[serde(rename = "n")]
+    NotEligible, // On loan at the club they'd play against
+    // Codes the game may add later count as available
+    #[default]
+    #[serde(rename = "a", other)]
+    Available,
+}
+
+impl Status {
+    // Players who won't play whatever their chance of playing says
+    pub fn is_out(&self) -> bool {
+        !matches!(self, Status::Available | Status::Doubtful)
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Status::Available => write!(f, "available"),
+            Status::Doubtful => write!(f, "doubtful"),
+            Status::Injured => write!(f, "injured"),
+            Status::Suspended => write!(f, "suspended"),
+            Status::Unavailable => write!(f, "unavailable"),
+            Status::NotEligible => write!(f, "not eligible"),
+        }
+    }
+}
+
+// What a player is ranked by
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    #[default]
+    ExpectedPoints,
+    Form,
+    TotalPoints,
+    Projected, // Sum of the imported projections
+    Model,     // Sum of the points the expected points model projects
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Metric::ExpectedPoints => write!(f, "expected_points"),
+            Metric::Form => write!(f, "form"),
+            Metric::TotalPoints => write!(f, "total_points"),
+            Metric::Projected => write!(f, "projected"),
+            Metric::Model => write!(f, "model"),
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "expected_points" => Ok(Metric::ExpectedPoints),
+            "form" => Ok(Metric::Form),
+            "total_points" => Ok(Metric::TotalPoints),
+            "projected" => Ok(Metric::Projected),
+            "model" => Ok(Metric::Model),
+            _ => Err(format!(
+                "unknown metric `{}`, expected one of: expected_points, form, total_points, projected, model",
+                s
+            )),
+        }
+    }
+}
+
+// What a player has done so far this season
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SeasonStats {
+    pub minutes: u32,
+    pub starts: u32,
+    pub goals_scored: u32,
+    pub assists: u32,
+    pub bonus: u32,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Player {
     pub name: String,
     pub team: Team,
     pub position: Position,
-    pub price: f32,
+    pub price: Money,
     pub id: u16,
     pub form: f32,
     pub health: f32,
     pub total_points: i32,
     pub expected_points: f32,
+    #[serde(default)]
+    pub status: Status,
+    // Why the player is doubtful or out, and since when
+    #[serde(default)]
+    pub news: String,
+    #[serde(default)]
+    pub news_added: Option<String>,
+    // Share of all managers who own the player, in percent
+    #[serde(default)]
+    pub selected_by_percent: f32,
+    // Points projected for each gameweek by an imported projections file
+    #[serde(skip)]
+    pub projections: BTreeMap<u8, f32>,
+    // Points for each gameweek from the expected points model
+    #[serde(skip)]
+    pub model_points: BTreeMap<u8, f32>,
+    #[serde(skip)]
+    pub stats: SeasonStats,
+    // Minutes played in the latest matches, oldest first, when they were fetched
+    #[serde(skip)]
+    pub recent_minutes: Vec<u32>,
+    #[serde(skip)]
+    pub expected_minutes: Option<ExpectedMinutes>,
+    // Whether expected points and projections are scaled by the expected minutes
+    #[serde(skip)]
+    minutes_discount: bool,
+    // What each metric of a blend was worth, when the metric is one
+    #[serde(skip)]
+    pub sources: Vec<(Metric, f32)>,
     metric: f32,
 }
 
@@ -38,15 +194,59 @@ impl Player {
     pub fn metric(&self) -> f32 {
         self.metric
     }
-    pub fn update_metric(&mut self) {
-        // self.metric = self.total_points as f32 * self.health;
-        // self.metric = self.form * self.health;
-        self.metric = self.expected_points;
+    pub fn value_of(&self, metric: Metric) -> f32 {
+        let availability = match self.expected_minutes {
+            Some(minutes) if self.minutes_discount => minutes.share(),
+            _ => 1.0,
+        };
+        match metric {
+            Metric::ExpectedPoints => self.expected_points * availability,
+            Metric::Form => self.form * self.health,
+            Metric::TotalPoints => self.total_points as f32 * self.health,
+            Metric::Projected => self.projections.values().sum::<f32>() * availability,
+            Metric::Model => self.model_points.values().sum(),
+        }
+    }
+    // Chance of playing at all in the next match, from the expected minutes when known
+    pub fn chance_of_playing(&self) -> f32 {
+        self.expected_minutes.map_or(self.health, |m| m.appears)
+    }
+    // Expected points and projections assume a full match, this scales them by
+    // the share of one the player is expected to play
+    pub fn set_minutes_discount(&mut self, discount: bool) {
+        self.minutes_discount = discount;
+    }
+    pub fn set_metric(&mut self, metric: Metric) {
+        self.metric = self.value_of(metric);
+        self.sources.clear();
     }
+    // The weighted average of the blend's metrics, keeping what each one was worth
+    pub fn set_blend(&mut self, blend: &Blend) {
+        self.sources = blend
+            .metrics()
+            .into_iter()
+            .map(|m| (m, self.value_of(m)))
+            .collect();
+        self.metric = blend.value(&self.position, |m| self.value_of(m));
+    }
+    // Share of managers owning the player, from 0 to 1
+    pub fn ownership(&self) -> f32 {
+        (self.selected_by_percent / 100.0).clamp(0.0, 1.0)
+    }
+    // The metric less `weight` times the share of it most managers also get, so
+    // players few own are worth more to someone trying to climb the ranks
+    pub fn differential_metric(&self, weight: f32) -> f32 {
+        self.metric * (1.0 - weight * self.ownership())
+    }
+    // Worth nothing whatever the metric says, so the optimizer sells them first
+    pub fn rule_out(&mut self) {
+        self.metric = 0.0;
+    }
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         form: f32,
         health: f32,
-        price: f32,
+        price: Money,
         name: String,
         position: Position,
         id: u16,
@@ -55,21 +255,48 @@ impl Player {
         expected_points: f32,
     ) -> Player {
         let mut player = Player {
-            form: form,
-            health: health,
-            price: price,
-            name: name,
-            position: position,
-            id: id,
-            team: team,
+            form,
+            health,
+            price,
+            name,
+            position,
+            id,
+            team,
             metric: 0.0,
             total_points: points,
-            expected_points: expected_points,
+            expected_points,
+            status: Status::default(),
+            news: String::new(),
+            news_added: None,
+            selected_by_percent: 0.0,
+            projections: BTreeMap::new(),
+            model_points: BTreeMap::new(),
+            stats: SeasonStats::default(),
+            recent_minutes: Vec::new(),
+            expected_minutes: None,
+            minutes_discount: false,
+            sources: Vec::new(),
         };
-        player.update_metric();
+        player.set_metric(Metric::default());
         player
     }
 }
+// One row per player with a header, the columns are the fields of `Player`
+pub fn write_csv<W: io::Write>(players: &[Player], writer: W) -> Result<(), Box<dyn Error>> {
+    let mut writer = csv::Writer::from_writer(writer);
+    for player in players {
+        writer.serialize(player)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn read_csv<R: io::Read>(reader: R) -> Result<Vec<Player>, Box<dyn Error>> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let players = reader.deserialize().collect::<Result<Vec<Player>, _>>()?;
+    Ok(players)
+}
+
 impl PartialEq for Player {
     fn eq(&self, other: &Player) -> bool {
         self.id == other.id
@@ -77,14 +304,15 @@ impl PartialEq for Player {
 }
 impl Eq for Player {}
 
-impl fmt::Debug for Player{
+impl fmt::Debug for Player {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.name)
     }
 }
-impl ToString for Player {
-    fn to_string(&self) -> String {
-        format!(
+impl fmt::Display for Player {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
             "{}, form: {:.2}, price: {:.2}, position: {}, team: {}, id: {}, health: {:.2}, points: {}, metric: {:.2}",
             self.name,
             self.form,
@@ -95,8 +323,92 @@ impl ToString for Player {
             self.health,
             self.total_points,
             self.metric,
+        )?;
+        if !self.sources.is_empty() {
+            let sources: Vec<String> = self
+                .sources
+                .iter()
+                .map(|(m, v)| format!("{} {:.2}", m, v))
+                .collect();
+            write!(f, " ({})", sources.join(", "))?;
+        }
+        if self.status != Status::Available {
+            write!(f, ", {}", self.status)?;
+            if !self.news.is_empty() {
+                write!(f, ": {}", self.news)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl Player {
+    // A player worth `metric` when they play, which they do with `chance`
+    pub fn with_chance(id: u16, position: Position, team: u8, metric: f32, chance: f32) -> Player {
+        let mut player = Player::new(
+            0.0,
+            1.0,
+            Money::from_tenths(50),
+            format!("Player{}", id),
+            position,
+            id,
+            Team::new(team),
+            0,
+            metric * chance,
+        );
+        player.expected_minutes = Some(ExpectedMinutes {
+            minutes: 90.0 * chance,
+            start: chance,
+            appears: chance,
+        });
+        player
+    }
+
+    // A player costing `price` tenths of a million and worth `metric`
+    pub fn with_price(id: u16, position: Position, team: u8, price: i32, metric: f32) -> Player {
+        Player::new(
+            0.0,
+            1.0,
+            Money::from_tenths(price),
+            format!("Player{}", id),
+            position,
+            id,
+            Team::new(team),
+            0,
+            metric,
         )
     }
+
+    // A player found by `name`, for the tests that match names
+    pub fn named(id: u16, name: &str, position: Position, team: u8) -> Player {
+        Player::new(
+            0.0,
+            1.0,
+            Money::from_tenths(50),
+            String::from(name),
+            position,
+            id,
+            Team::new(team),
+            0,
+            0.0,
+        )
+    }
+}
+
+// Players whose names only differ in accents, spelling or team
+#[cfg(test)]
+pub fn named_players() -> Vec<Player> {
+    vec![
+        Player::named(1, "Martínez", Position::GK, 2),
+        Player::named(2, "Martinez", Position::DEF, 13),
+        Player::named(3, "Lisandro", Position::DEF, 13),
+        Player::named(4, "Robertson", Position::DEF, 11),
+        Player::named(5, "B.Fernandes", Position::MID, 13),
+        Player::named(6, "Ward-Prowse", Position::MID, 16),
+        Player::named(7, "Son", Position::MID, 17),
+        Player::named(8, "Salah", Position::MID, 11),
+    ]
 }
 
 #[cfg(test)]
@@ -108,7 +420,7 @@ mod tests {
         let mut player = Player::new(
             7.2,
             0.8,
-            1.0,
+            Money::from_tenths(10),
             String::from("Lampard"),
             Position::MID,
             1,
@@ -116,11 +428,11 @@ mod tests {
             5,
             5.0,
         );
-        player.metric = player.form *  player.health;
+        player.metric = player.form * player.health;
 
         assert_eq!(player.form, 7.2);
         assert_eq!(player.health, 0.8);
-        assert_eq!(player.price, 1.0);
+        assert_eq!(player.price, Money::from_tenths(10));
         assert_eq!(player.name, "Lampard");
         assert_eq!(player.id, 1);
         assert_eq!(player.metric, player.metric());
@@ -133,7 +445,7 @@ mod tests {
         let same_id_player = Player::new(
             7.2,
             1.0,
-            1.1,
+            Money::from_tenths(11),
             String::from("Terry"),
             Position::DEF,
             1,
@@ -143,4 +455,115 @@ mod tests {
         );
         assert_eq!(player, same_id_player);
     }
+
+    #[test]
+    fn test_metric() {
+        let mut player = Player::new(
+            4.0,
+            0.5,
+            Money::from_tenths(55),
+            String::from("Lampard"),
+            Position::MID,
+            1,
+            Team::new(6),
+            60,
+            6.5,
+        );
+        assert_eq!(player.metric(), 6.5);
+        player.set_metric(Metric::Form);
+        assert_eq!(player.metric(), 2.0);
+        player.set_metric("total_points".parse().unwrap());
+        assert_eq!(player.metric(), 30.0);
+        player.set_metric(Metric::Projected);
+        assert_eq!(player.metric(), 0.0);
+        player.projections = vec![(13, 4.5), (14, 2.0)].into_iter().collect();
+        player.set_metric(Metric::Projected);
+        assert_eq!(player.metric(), 6.5);
+        assert!("points".parse::<Metric>().is_err());
+
+        let blend: Blend = "expected_points=1,form=1; MID: form=1".parse().unwrap();
+        player.set_blend(&blend);
+        assert_eq!(player.metric(), 2.0);
+        assert_eq!(
+            player.sources,
+            vec![(Metric::ExpectedPoints, 6.5), (Metric::Form, 2.0)]
+        );
+        assert!(player
+            .to_string()
+            .ends_with("metric: 2.00 (expected_points 6.50, form 2.00)"));
+        player.position = Position::FWD;
+        player.set_blend(&blend);
+        assert_eq!(player.metric(), 4.25);
+        player.set_metric(Metric::Form);
+        assert!(player.sources.is_empty());
+
+        player.expected_minutes = Some(ExpectedMinutes {
+            minutes: 45.0,
+            start: 0.5,
+            appears: 0.5,
+        });
+        assert_eq!(player.value_of(Metric::ExpectedPoints), 6.5);
+        player.set_minutes_discount(true);
+        assert_eq!(player.value_of(Metric::ExpectedPoints), 3.25);
+        assert_eq!(player.value_of(Metric::Form), 2.0);
+    }
+
+    #[test]
+    fn test_serialization() {
+        let mut player = Player::new(
+            4.0,
+            0.5,
+            Money::from_tenths(55),
+            String::from("Martínez, \"Dibu\""),
+            Position::GK,
+            17,
+            Team::new(2),
+            60,
+            6.5,
+        );
+        player.set_metric(Metric::Form);
+        player.status = Status::Doubtful;
+        player.news = String::from("Knee injury - 50% chance of playing");
+        assert!(player
+            .to_string()
+            .ends_with("metric: 2.00, doubtful: Knee injury - 50% chance of playing"));
+        player.selected_by_percent = 40.0;
+        assert_eq!(player.differential_metric(0.5), 1.6);
+        assert_eq!(player.differential_metric(0.0), 2.0);
+
+        let json = serde_json::to_value(&player).unwrap();
+        assert_eq!(json["position"], "GK");
+        assert_eq!(json["status"], "d");
+        assert_eq!(json["team"], 2);
+        assert_eq!(json["metric"], 2.0);
+        let from_json: Player = serde_json::from_value(json).unwrap();
+        assert_eq!(from_json.to_string(), player.to_string());
+        assert_eq!(from_json.expected_points, 6.5);
+
+        let mut csv = Vec::new();
+        write_csv(&[player.clone(), player.clone()], &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.starts_with(
+            "name,team,position,price,id,form,health,total_points,expected_points,status,news,news_added,selected_by_percent,metric\n"
+        ));
+        let from_csv = read_csv(csv.as_bytes()).unwrap();
+        assert_eq!(from_csv.len(), 2);
+        assert_eq!(from_csv[1].to_string(), player.to_string());
+        assert_eq!(from_csv[1].expected_points, 6.5);
+        assert_eq!(from_csv[1].status, Status::Doubtful);
+        assert_eq!(from_csv[1].news_added, None);
+
+        // Files written before players had a status, and codes the game may add later
+        let old = "name,team,position,price,id,form,health,total_points,expected_points,metric\n\
+            Raya,1,GK,5.5,3,4.0,1.0,60,6.5,4.0\n";
+        assert_eq!(
+            read_csv(old.as_bytes()).unwrap()[0].status,
+            Status::Available
+        );
+        assert_eq!(
+            serde_json::from_str::<Status>("\"x\"").unwrap(),
+            Status::Available
+        );
+        assert!(Status::Suspended.is_out() && !Status::Doubtful.is_out());
+    }
 }
@@ -0,0 +1,231 @@
+use crate::player::Player;
+use crate::squad_file::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, PartialEq)]
+pub struct ProjectionsError(String);
+impl fmt::Display for ProjectionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error in projections file: {}", self.0)
+    }
+}
+impl Error for ProjectionsError {}
+
+// Points projected for each gameweek, by player id, and why the rows that
+// matched no player were skipped
+#[derive(Debug, Default)]
+pub struct Projections {
+    pub by_player: HashMap<u16, BTreeMap<u8, f32>>,
+    pub unmatched: Vec<String>,
+}
+
+// `gw13`, `GW 13`, `gameweek13` or just `13`
+fn gameweek_column(header: &str) -> Option<u8> {
+    let header = header.trim().to_lowercase();
+    let number = header
+        .strip_prefix("gameweek")
+        .or_else(|| header.strip_prefix("gw"))
+        .unwrap_or(&header);
+    number.trim().parse().ok()
+}
+
+impl Projections {
+    // A CSV file with an `id` column, or `name` plus optional `team` and
+    // `position` columns for rows without one, and a column per gameweek.
+    // Rows whose id isn't in `list` fall back to the name. Other columns and
+    // empty cells are ignored.
+    pub fn parse<R: io::Read>(reader: R, list: &[Player]) -> Result<Projections, Box<dyn Error>> {
+        let mut reader = csv::Reader::from_reader(reader);
+        let headers = reader
+            .headers()
+            .map_err(|e| ProjectionsError(e.to_string()))?
+            .clone();
+        let column = |name: &str| {
+            headers
+                .iter()
+                .position(|h| h.trim().eq_ignore_ascii_case(name))
+        };
+        let (id, name, team, position) = (
+            column("id"),
+            column("name"),
+            column("team"),
+            column("position"),
+        );
+        if id.is_none() && name.is_none() {
+            return Err(Box::new(ProjectionsError(String::from(
+                "it needs an `id` or a `name` column",
+            ))));
+        }
+        let gameweeks: Vec<(usize, u8)> = headers
+            .iter()
+            .enumerate()
+            .filter_map(|(i, h)| gameweek_column(h).map(|gw| (i, gw)))
+            .collect();
+        if gameweeks.is_empty() {
+            return Err(Box::new(ProjectionsError(String::from(
+                "it has no gameweek columns like `gw13`",
+            ))));
+        }
+
+        let mut projections = Projections::default();
+        let mut rows: HashMap<u16, usize> = HashMap::new();
+        for (n, record) in reader.records().enumerate() {
+            let row = n + 2; // Counting the header, like a spreadsheet
+            let record = record.map_err(|e| ProjectionsError(e.to_string()))?;
+            let cell = |column: Option<usize>| {
+                column
+                    .and_then(|i| record.get(i))
+                    .map(str::trim)
+                    .filter(|c| !c.is_empty())
+                    .map(String::from)
+            };
+            let by_id = cell(id)
+                .map(|id| {
+                    id.parse::<u16>()
+                        .map_err(|_| ProjectionsError(format!("row {}: bad id `{}`", row, id)))
+                })
+                .transpose()?
+                .and_then(|id| list.iter().find(|p| p.id == id));
+            let entry = Entry {
+                name: cell(name),
+                team: cell(team),
+                position: cell(position),
+                id: if cell(name).is_some() {
+                    None
+                } else {
+                    cell(id).and_then(|id| id.parse().ok())
+                },
+            };
+            let player = match by_id.map_or_else(|| entry.find(list), Ok) {
+                Ok(player) => player,
+                Err(e) => {
+                    projections.unmatched.push(format!("row {}: {}", row, e));
+                    continue;
+                }
+            };
+            if let Some(first) = rows.get(&player.id) {
+                projections.unmatched.push(format!(
+                    "row {}: {} was already projected on row {}",
+                    row, player.name, first
+                ));
+                continue;
+            }
+            rows.insert(player.id, row);
+
+            let mut points = BTreeMap::new();
+            for (i, gameweek) in &gameweeks {
+                if let Some(value) = cell(Some(*i)) {
+                    let value = value.parse::<f32>().map_err(|_| {
+                        ProjectionsError(format!(
+                            "row {}: `{}` is not a number of points for gameweek {}",
+                            row, value, gameweek
+                        ))
+                    })?;
+                    points.insert(*gameweek, value);
+                }
+            }
+            projections.by_player.insert(player.id, points);
+        }
+        Ok(projections)
+    }
+
+    pub fn load(path: &Path, list: &[Player]) -> Result<Projections, Box<dyn Error>> {
+        let file = fs::File::open(path)
+            .map_err(|e| ProjectionsError(format!("could not read {}: {}", path.display(), e)))?;
+        Projections::parse(file, list)
+    }
+
+    // Players without a row are left without projections
+    pub fn apply(&self, list: &mut [Player]) {
+        for player in list.iter_mut() {
+            if let Some(points) = self.by_player.get(&player.id) {
+                player.projections = points.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::{named_players, Metric};
+
+    #[test]
+    fn test_gameweek_column() {
+        assert_eq!(gameweek_column("gw13"), Some(13));
+        assert_eq!(gameweek_column("GW 7"), Some(7));
+        assert_eq!(gameweek_column("Gameweek 38"), Some(38));
+        assert_eq!(gameweek_column("21"), Some(21));
+        assert_eq!(gameweek_column("price"), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let csv = "id,name,team,price,GW13,GW14\n\
+            8,,,13.0,7.5,6.1\n\
+            ,Son,,10.0,5.0,\n\
+            99,Martinez,villa,5.0,4.0,3.5\n\
+            ,Martinez,,5.0,1.0,1.0\n\
+            ,Haaland,,15.0,9.0,8.0\n\
+            ,Salah,,13.0,1.0,1.0\n";
+        let mut list = named_players();
+        let projections = Projections::parse(csv.as_bytes(), &list).unwrap();
+        assert_eq!(projections.by_player.len(), 3);
+        assert_eq!(
+            projections.by_player[&8],
+            vec![(13, 7.5), (14, 6.1)].into_iter().collect()
+        );
+        // Empty cells are gameweeks without a projection
+        assert_eq!(
+            projections.by_player[&7],
+            vec![(13, 5.0)].into_iter().collect()
+        );
+        // An unknown id falls back to the name and team
+        assert_eq!(projections.by_player[&1].len(), 2);
+
+        assert_eq!(projections.unmatched.len(), 3);
+        assert!(projections.unmatched[0].starts_with("row 5: `Martinez` is ambiguous"));
+        assert!(projections.unmatched[1].starts_with("row 6: no player matches `Haaland`"));
+        assert_eq!(
+            projections.unmatched[2],
+            "row 7: Salah was already projected on row 2"
+        );
+
+        projections.apply(&mut list);
+        list.iter_mut()
+            .for_each(|p| p.set_metric(Metric::Projected));
+        assert!((list[7].metric() - 13.6).abs() < 1e-4);
+        assert_eq!(list[1].metric(), 0.0);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let list = named_players();
+        let error = |csv: &str| {
+            Projections::parse(csv.as_bytes(), &list)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error("team,gw1\n11,3.0\n"),
+            "Error in projections file: it needs an `id` or a `name` column"
+        );
+        assert_eq!(
+            error("id,points\n3,3.0\n"),
+            "Error in projections file: it has no gameweek columns like `gw13`"
+        );
+        assert_eq!(
+            error("id,gw1\n3,lots\n"),
+            "Error in projections file: row 2: `lots` is not a number of points for gameweek 1"
+        );
+        assert_eq!(
+            error("id,gw1\nthree,3.0\n"),
+            "Error in projections file: row 2: bad id `three`"
+        );
+    }
+}
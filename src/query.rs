@@ -0,0 +1,208 @@
+use crate::money::Money;
+use crate::player::{Player, Position};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// What `players list` orders its results by
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SortKey {
+    #[default]
+    Metric,
+    Price,
+    Value, // Metric per million
+    Form,
+    Points,
+    Health,
+    Fixtures,
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "metric" => Ok(SortKey::Metric),
+            "price" => Ok(SortKey::Price),
+            "value" => Ok(SortKey::Value),
+            "form" => Ok(SortKey::Form),
+            "points" => Ok(SortKey::Points),
+            "health" => Ok(SortKey::Health),
+            "fixtures" => Ok(SortKey::Fixtures),
+            _ => Err(format!(
+                "unknown sort key `{}`, expected one of: metric, price, value, form, points, health, fixtures",
+                s
+            )),
+        }
+    }
+}
+
+pub fn value(player: &Player) -> f32 {
+    if player.price > Money::ZERO {
+        player.metric() / player.price.millions()
+    } else {
+        0.0
+    }
+}
+
+// Filters and ordering of the `players list` command. Empty lists and `None`
+// bounds don't filter anything.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerQuery {
+    pub positions: Vec<Position>,
+    pub teams: Vec<String>, // Team ids or case insensitive parts of team names
+    pub min_price: Option<Money>,
+    pub max_price: Option<Money>,
+    pub min_health: Option<f32>,
+    pub min_metric: Option<f32>,
+    pub max_metric: Option<f32>,
+    pub max_difficulty: Option<f32>,
+    pub horizon: usize, // Fixtures averaged for --max-difficulty and sorting by fixtures
+    pub sort: SortKey,
+    pub reverse: bool,
+    pub count: usize,
+}
+
+impl Default for PlayerQuery {
+    fn default() -> Self {
+        PlayerQuery {
+            positions: Vec::new(),
+            teams: Vec::new(),
+            min_price: None,
+            max_price: None,
+            min_health: None,
+            min_metric: None,
+            max_metric: None,
+            max_difficulty: None,
+            horizon: 3,
+            sort: SortKey::default(),
+            reverse: false,
+            count: 20,
+        }
+    }
+}
+
+fn within<T: PartialOrd>(value: T, min: Option<T>, max: Option<T>) -> bool {
+    min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max)
+}
+
+impl PlayerQuery {
+    // Fixture difficulties are only fetched when the query uses them
+    pub fn needs_fixtures(&self) -> bool {
+        self.max_difficulty.is_some() || self.sort == SortKey::Fixtures
+    }
+
+    fn matches(&self, player: &Player, difficulty: Option<f32>) -> bool {
+        (self.positions.is_empty() || self.positions.contains(&player.position))
+            && (self.teams.is_empty() || self.teams.iter().any(|t| player.team.matches(t)))
+            && within(player.price, self.min_price, self.max_price)
+            && within(player.health, self.min_health, None)
+            && within(player.metric(), self.min_metric, self.max_metric)
+            && match self.max_difficulty {
+                Some(max) => difficulty.is_some_and(|d| d <= max),
+                None => true,
+            }
+    }
+
+    // Higher is better for every key but fixtures, where an easier run is better.
+    // Teams without upcoming fixtures sort last, reversed or not.
+    fn ordering(&self, a: (&Player, Option<f32>), b: (&Player, Option<f32>)) -> Ordering {
+        let key = |(player, difficulty): (&Player, Option<f32>)| match self.sort {
+            SortKey::Metric => Some(player.metric()),
+            SortKey::Price => Some(player.price.millions()),
+            SortKey::Value => Some(value(player)),
+            SortKey::Form => Some(player.form),
+            SortKey::Points => Some(player.total_points as f32),
+            SortKey::Health => Some(player.health),
+            SortKey::Fixtures => difficulty.map(|d| -d),
+        };
+        match (key(a), key(b)) {
+            (Some(a), Some(b)) => {
+                let ordering = b.partial_cmp(&a).unwrap_or(Ordering::Equal);
+                if self.reverse {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            }
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+
+    // Matching players in order, at most `count` of them. Ties keep the order of `players`.
+    pub fn run<'a>(&self, players: &'a [Player], difficulty: &HashMap<u8, f32>) -> Vec<&'a Player> {
+        let difficulty_of = |player: &Player| difficulty.get(&player.team.id()).cloned();
+        let mut result: Vec<&Player> = players
+            .iter()
+            .filter(|p| self.matches(p, difficulty_of(p)))
+            .collect();
+        result.sort_by(|a, b| self.ordering((a, difficulty_of(a)), (b, difficulty_of(b))));
+        result.truncate(self.count);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(players: Vec<&Player>) -> Vec<u16> {
+        players.iter().map(|p| p.id).collect()
+    }
+
+    #[test]
+    fn test_query() {
+        let players = vec![
+            Player::with_price(1, Position::DEF, 1, 60, 6.0),
+            Player::with_price(2, Position::DEF, 11, 45, 4.0),
+            Player::with_price(3, Position::MID, 11, 100, 8.0),
+            Player::with_price(4, Position::DEF, 12, 50, 3.0),
+            Player::with_price(5, Position::DEF, 20, 40, 3.0),
+        ];
+        let difficulty: HashMap<u8, f32> = [(1, 4.0), (11, 2.0), (12, 2.5)].into();
+
+        // All players, best metric first
+        let query = PlayerQuery::default();
+        assert_eq!(ids(query.run(&players, &difficulty)), vec![3, 1, 2, 4, 5]);
+
+        // Defenders under 5.0 by value, from teams with easy fixtures
+        let query = PlayerQuery {
+            positions: vec![Position::DEF],
+            max_price: Some(Money::from_tenths(50)),
+            max_difficulty: Some(3.0),
+            sort: SortKey::Value,
+            ..PlayerQuery::default()
+        };
+        assert_eq!(ids(query.run(&players, &difficulty)), vec![2, 4]);
+
+        let query = PlayerQuery {
+            teams: vec![String::from("liver"), String::from("20")],
+            sort: SortKey::Price,
+            reverse: true,
+            count: 2,
+            ..PlayerQuery::default()
+        };
+        assert_eq!(ids(query.run(&players, &difficulty)), vec![5, 2]);
+
+        let query = PlayerQuery {
+            sort: SortKey::Fixtures,
+            min_metric: Some(3.5),
+            ..PlayerQuery::default()
+        };
+        assert_eq!(ids(query.run(&players, &difficulty)), vec![2, 3, 1]);
+
+        // Hardest fixtures first, player 5's team without fixtures still last
+        let query = PlayerQuery {
+            sort: SortKey::Fixtures,
+            reverse: true,
+            ..PlayerQuery::default()
+        };
+        assert_eq!(ids(query.run(&players, &difficulty)), vec![1, 4, 2, 3, 5]);
+        let query = PlayerQuery {
+            sort: SortKey::Fixtures,
+            ..PlayerQuery::default()
+        };
+        assert_eq!(ids(query.run(&players, &difficulty)), vec![2, 3, 4, 1, 5]);
+    }
+}
@@ -0,0 +1,606 @@
+use crate::api::LeagueEntry;
+use crate::autosub;
+use crate::captaincy::{CaptainOption, CAPTAINCY_OPTIONS};
+use crate::league::{Exposure, RankTransfer};
+use crate::minutes::ExpectedMinutes;
+use crate::money::Money;
+use crate::player::Player;
+use crate::query;
+use crate::simulation::{self, Distribution, SimulationSettings, PERCENTILES};
+use crate::squad::Squad;
+use crate::transfer;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+// Bumped whenever a field is renamed or removed, adding fields keeps the version
+pub const SCHEMA_VERSION: u32 = 1;
+const LEAGUE_EXPOSURES: usize = 10; // Players listed as exposures and as differentials
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("unknown output `{}`, expected text or json", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ReportPlayer {
+    pub id: u16,
+    pub name: String,
+    pub team: String,
+    pub position: String,
+    pub price: Money,
+    pub metric: f32,
+    pub status: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub news: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub news_added: Option<String>,
+    pub selected_by_percent: f32,
+    // What the metrics of a blended metric were worth
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub sources: BTreeMap<String, f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_minutes: Option<ExpectedMinutes>,
+}
+
+impl From<&Player> for ReportPlayer {
+    fn from(player: &Player) -> Self {
+        ReportPlayer {
+            id: player.id,
+            name: player.name.clone(),
+            team: player.team.to_string(),
+            position: player.position.to_string(),
+            price: player.price,
+            metric: player.metric(),
+            status: player.status.to_string(),
+            news: player.news.clone(),
+            news_added: player.news_added.clone(),
+            selected_by_percent: player.selected_by_percent,
+            sources: player
+                .sources
+                .iter()
+                .map(|(m, v)| (m.to_string(), *v))
+                .collect(),
+            expected_minutes: player.expected_minutes,
+        }
+    }
+}
+
+// A `players list` result, with the columns the list can be sorted by
+#[derive(Serialize, Debug)]
+pub struct PlayerRow {
+    #[serde(flatten)]
+    pub player: ReportPlayer,
+    pub value: f32,
+    pub form: f32,
+    pub health: f32,
+    pub total_points: i32,
+    pub fixture_difficulty: Option<f32>,
+}
+
+impl PlayerRow {
+    pub fn new(player: &Player, fixture_difficulty: Option<f32>) -> Self {
+        PlayerRow {
+            player: ReportPlayer::from(player),
+            value: query::value(player),
+            form: player.form,
+            health: player.health,
+            total_points: player.total_points,
+            fixture_difficulty,
+        }
+    }
+}
+
+pub fn player_table(rows: &[PlayerRow]) -> String {
+    let mut table = format!(
+        "{:>4}  {:<20} {:<22} {:<4} {:>5} {:>6} {:>5} {:>5} {:>6} {:>6} {:>4}  {}\n",
+        "id",
+        "name",
+        "team",
+        "pos",
+        "price",
+        "metric",
+        "value",
+        "form",
+        "health",
+        "points",
+        "fdr",
+        "status"
+    );
+    for row in rows {
+        let p = &row.player;
+        table += &format!(
+            "{:>4}  {:<20} {:<22} {:<4} {:>5.1} {:>6.2} {:>5.2} {:>5.1} {:>6.2} {:>6} {:>4}  {}\n",
+            p.id,
+            p.name,
+            p.team,
+            p.position,
+            p.price,
+            p.metric,
+            row.value,
+            row.form,
+            row.health,
+            row.total_points,
+            row.fixture_difficulty
+                .map(|d| format!("{:.1}", d))
+                .unwrap_or_else(|| String::from("-")),
+            p.status,
+        );
+    }
+    table
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReportTransfer {
+    pub out: ReportPlayer,
+    #[serde(rename = "in")]
+    pub in_: ReportPlayer,
+}
+
+// A captain and vice pair and the points the armband is expected to bring
+#[derive(Serialize, Debug)]
+pub struct ReportCaptaincy {
+    pub captain: ReportPlayer,
+    pub vice_captain: Option<ReportPlayer>,
+    pub expected_points: f32,
+    pub stdev: f32,
+}
+
+impl From<&CaptainOption> for ReportCaptaincy {
+    fn from(option: &CaptainOption) -> Self {
+        ReportCaptaincy {
+            captain: ReportPlayer::from(&option.captain),
+            vice_captain: option.vice_captain.as_ref().map(ReportPlayer::from),
+            expected_points: option.expected_points,
+            stdev: option.stdev,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Objective {
+    pub lineup_metric: f32,
+    pub auto_sub_points: f32, // What the bench is expected to add, not part of the total
+    pub hit_cost: f32,
+    // Mean points of the simulated gameweeks, with the simulated objective
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub simulated_points: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub simulated_stdev: Option<f32>,
+    pub total: f32, // What the squad was ranked by, less hits
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct SearchStats {
+    pub pool_size: usize,
+    pub squads_checked: usize,
+    pub top_found_after: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Recommendation {
+    pub schema_version: u32,
+    pub lineup: Vec<ReportPlayer>,
+    pub bench: Vec<ReportPlayer>,
+    pub captain: ReportPlayer,
+    pub vice_captain: Option<ReportPlayer>,
+    pub captaincy: Vec<ReportCaptaincy>, // Best first
+    // Starters who may well not play 60 minutes
+    pub rotation_risks: Vec<ReportPlayer>,
+    pub transfers: Vec<ReportTransfer>,
+    pub hits: usize,
+    pub bank: Money,
+    pub objective: Objective,
+    pub search: SearchStats,
+}
+
+fn report_players(players: &[Player]) -> Vec<ReportPlayer> {
+    players.iter().map(ReportPlayer::from).collect()
+}
+
+impl Recommendation {
+    pub fn new(
+        squad: &Squad,
+        current_squad: &Squad,
+        free_transfers: usize,
+        transfer_cost: f32,
+        captain_upside: f32,
+        search: SearchStats,
+    ) -> Self {
+        let lineup = squad.best_starter_lineup();
+        let captaincy = squad.captaincy(captain_upside);
+        let best = captaincy.first().expect("An empty squad has no captain");
+        let transfers = squad.transfers_from(current_squad);
+        let hits = transfer::hits(&transfers, free_transfers);
+        let lineup_metric = lineup.total_metric(squad.rules().captain_multiplier);
+        let hit_cost = hits as f32 * transfer_cost;
+        Recommendation {
+            schema_version: SCHEMA_VERSION,
+            lineup: report_players(&lineup.organized_players()),
+            bench: report_players(&squad.ordered_bench()),
+            captain: ReportPlayer::from(&best.captain),
+            vice_captain: best.vice_captain.as_ref().map(ReportPlayer::from),
+            captaincy: captaincy
+                .iter()
+                .take(CAPTAINCY_OPTIONS)
+                .map(ReportCaptaincy::from)
+                .collect(),
+            rotation_risks: squad
+                .rotation_risks()
+                .iter()
+                .map(|(p, _)| ReportPlayer::from(p))
+                .collect(),
+            transfers: transfers
+                .iter()
+                .map(|t| ReportTransfer {
+                    out: ReportPlayer::from(&t.out),
+                    in_: ReportPlayer::from(&t.in_),
+                })
+                .collect(),
+            hits,
+            bank: squad.max_cost() - squad.total_cost(),
+            objective: Objective {
+                lineup_metric,
+                auto_sub_points: squad.auto_sub_points(),
+                hit_cost,
+                simulated_points: None,
+                simulated_stdev: None,
+                total: lineup_metric - hit_cost,
+            },
+            search,
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct SquadReport {
+    pub players: Vec<ReportPlayer>,
+    pub cost: Money,
+    pub bank: Money,
+}
+
+impl SquadReport {
+    pub fn new(squad: &Squad) -> Self {
+        SquadReport {
+            players: report_players(&squad.organized_players()),
+            cost: squad.total_cost(),
+            bank: squad.max_cost() - squad.total_cost(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct RivalSimulation {
+    pub user_id: u32,
+    pub mean: f32,
+    pub stdev: f32,
+    pub chance_to_beat: f32, // Share of the runs the squad outscored the rival
+}
+
+// Points the squad scored over the simulated gameweeks
+#[derive(Serialize, Debug)]
+pub struct SimulationReport {
+    pub runs: usize,
+    pub seed: u64,
+    pub mean: f32,
+    pub stdev: f32,
+    pub percentiles: BTreeMap<u8, f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rival: Option<RivalSimulation>,
+}
+
+impl SimulationReport {
+    // `points` and the rival's are run by run, from the same simulation
+    pub fn new(
+        settings: &SimulationSettings,
+        points: &[f32],
+        rival: Option<(u32, &[f32])>,
+    ) -> Self {
+        let distribution = Distribution::new(points);
+        SimulationReport {
+            runs: settings.runs,
+            seed: settings.seed,
+            mean: distribution.mean,
+            stdev: distribution.stdev,
+            percentiles: PERCENTILES
+                .iter()
+                .map(|p| (*p, distribution.percentile(*p)))
+                .collect(),
+            rival: rival.map(|(user_id, rival_points)| {
+                let rival = Distribution::new(rival_points);
+                RivalSimulation {
+                    user_id,
+                    mean: rival.mean,
+                    stdev: rival.stdev,
+                    chance_to_beat: simulation::chance_to_beat(points, rival_points),
+                }
+            }),
+        }
+    }
+}
+
+// A player's multiplier in our lineup against the league's effective ownership
+#[derive(Serialize, Debug)]
+pub struct ReportOwnership {
+    pub player: ReportPlayer,
+    pub multiplier: f32,
+    pub effective_ownership: f32,
+    pub swing: f32, // Points we're expected to gain on the league through them
+}
+
+impl From<&Exposure> for ReportOwnership {
+    fn from(exposure: &Exposure) -> Self {
+        ReportOwnership {
+            player: ReportPlayer::from(&exposure.player),
+            multiplier: exposure.ours,
+            effective_ownership: exposure.league,
+            swing: exposure.swing(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReportRankTransfer {
+    pub out: ReportPlayer,
+    #[serde(rename = "in")]
+    pub in_: ReportPlayer,
+    pub expected_rank: f32,
+}
+
+// Where the squad stands in a classic league and the transfers that move it up
+#[derive(Serialize, Debug)]
+pub struct LeagueReport {
+    pub league_id: u32,
+    pub name: String,
+    pub standings: Vec<LeagueEntry>,
+    pub expected_rank: f32, // Rank after the gameweek without transfers
+    pub exposed: Vec<ReportOwnership>,
+    pub differentials: Vec<ReportOwnership>,
+    pub transfers: Vec<ReportRankTransfer>,
+}
+
+impl LeagueReport {
+    // `exposures` biggest swing first, as league::exposures sorts them
+    pub fn new(
+        league_id: u32,
+        name: String,
+        standings: Vec<LeagueEntry>,
+        expected_rank: f32,
+        exposures: &[Exposure],
+        transfers: &[RankTransfer],
+    ) -> Self {
+        let listed = |differential: bool| -> Vec<ReportOwnership> {
+            exposures
+                .iter()
+                .filter(|e| e.swing() != 0.0 && (e.swing() > 0.0) == differential)
+                .take(LEAGUE_EXPOSURES)
+                .map(ReportOwnership::from)
+                .collect()
+        };
+        LeagueReport {
+            league_id,
+            name,
+            standings,
+            expected_rank,
+            exposed: listed(false),
+            differentials: listed(true),
+            transfers: transfers
+                .iter()
+                .map(|t| ReportRankTransfer {
+                    out: ReportPlayer::from(&t.transfer.out),
+                    in_: ReportPlayer::from(&t.transfer.in_),
+                    expected_rank: t.expected_rank,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct BacktestReport {
+    pub gameweek: u8,
+    pub lineup: Vec<ReportPlayer>,
+    pub captain: ReportPlayer,
+    pub vice_captain: Option<ReportPlayer>,
+    pub auto_subs: Vec<ReportTransfer>,
+    pub predicted: f32,
+    pub points: i32,
+    pub actual_points: i32,
+}
+
+impl BacktestReport {
+    // Scores the best lineup of `squad` with the points and minutes in `live`,
+    // bringing on the ordered bench for starters who didn't play and handing the
+    // armband to the vice-captain when the captain didn't
+    pub fn new(
+        gameweek: u8,
+        squad: &Squad,
+        live: &HashMap<u16, (i32, u32)>,
+        actual_points: i32,
+        captain_upside: f32,
+    ) -> Self {
+        let lineup = squad.best_starter_lineup();
+        let captaincy = squad.captaincy(captain_upside).remove(0);
+        let captain_multiplier = squad.rules().captain_multiplier;
+        let points_of = |player: &Player| live.get(&player.id).map_or(0, |(points, _)| *points);
+        let played = |player: &Player| {
+            live.get(&player.id)
+                .is_some_and(|(_, minutes)| *minutes > 0)
+        };
+        let auto_subs = autosub::auto_subs(
+            &lineup.organized_players(),
+            &squad.ordered_bench(),
+            &squad.rules().formation,
+            played,
+        );
+        let armband = std::iter::once(&captaincy.captain)
+            .chain(captaincy.vice_captain.as_ref())
+            .find(|p| played(p));
+        let points = lineup.players.iter().map(points_of).sum::<i32>()
+            + auto_subs.iter().map(|(_, in_)| points_of(in_)).sum::<i32>()
+            + (captain_multiplier as i32 - 1) * armband.map_or(0, points_of);
+        BacktestReport {
+            gameweek,
+            lineup: report_players(&lineup.organized_players()),
+            captain: ReportPlayer::from(&captaincy.captain),
+            vice_captain: captaincy.vice_captain.as_ref().map(ReportPlayer::from),
+            auto_subs: auto_subs
+                .iter()
+                .map(|(out, in_)| ReportTransfer {
+                    out: ReportPlayer::from(out),
+                    in_: ReportPlayer::from(in_),
+                })
+                .collect(),
+            predicted: lineup.total_metric(captain_multiplier),
+            points,
+            actual_points,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::Status;
+
+    #[test]
+    fn test_recommendation() {
+        let current: Vec<u16> = (1..=15).collect();
+        let mut new = current.clone();
+        new[7] = 20; // MID 8 -> 20
+        new[14] = 30; // FWD 15 -> 30
+        let recommendation = Recommendation::new(
+            &Squad::with_ids(&new),
+            &Squad::with_ids(&current),
+            1,
+            4.0,
+            0.0,
+            SearchStats::default(),
+        );
+        assert_eq!(recommendation.lineup.len(), 11);
+        assert_eq!(recommendation.bench.len(), 4);
+        assert_eq!(recommendation.captain.id, 30);
+        assert_eq!(recommendation.vice_captain.as_ref().unwrap().id, 20);
+        assert_eq!(recommendation.captaincy.len(), 3);
+        assert_eq!(recommendation.captaincy[0].captain.id, 30);
+        assert_eq!(recommendation.captaincy[0].expected_points, 60.0);
+        assert_eq!(recommendation.hits, 1);
+        assert_eq!(recommendation.objective.hit_cost, 4.0);
+        assert_eq!(recommendation.bank, Money::from_tenths(250));
+
+        let transfers: Vec<(u16, u16)> = recommendation
+            .transfers
+            .iter()
+            .map(|t| (t.out.id, t.in_.id))
+            .collect();
+        assert_eq!(transfers, vec![(8, 20), (15, 30)]);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&recommendation.to_json().unwrap()).unwrap();
+        assert_eq!(json["schema_version"], 1);
+        assert_eq!(json["transfers"][0]["in"]["name"], "Player20");
+        assert_eq!(json["transfers"][0]["out"]["position"], "MID");
+        assert_eq!(json["search"]["squads_checked"], 0);
+    }
+
+    #[test]
+    fn test_backtest() {
+        let squad = Squad::with_ids(&(1..=15).collect::<Vec<u16>>());
+        // Lineup is 1-3-4-3 here: GK 2, DEF 5-7, MID 9-12, FWD 13-15, captain 15
+        let mut live: HashMap<u16, (i32, u32)> = (1..=15).map(|id| (id, (1, 90))).collect();
+        let report = BacktestReport::new(3, &squad, &live, 50, 0.0);
+        assert_eq!(report.lineup.len(), 11);
+        assert_eq!(report.captain.id, 15);
+        assert_eq!(report.points, 12);
+        assert_eq!(report.actual_points, 50);
+        assert!(report.auto_subs.is_empty());
+
+        // Midfielder 9 didn't play, the best outfield player on the bench does
+        live.insert(9, (0, 0));
+        live.insert(8, (6, 90));
+        let report = BacktestReport::new(3, &squad, &live, 50, 0.0);
+        assert_eq!(report.auto_subs.len(), 1);
+        assert_eq!(
+            (report.auto_subs[0].out.id, report.auto_subs[0].in_.id),
+            (9, 8)
+        );
+        assert_eq!(report.points, 17);
+
+        // The vice-captain takes the armband from a captain who didn't play
+        assert_eq!(report.vice_captain.as_ref().unwrap().id, 14);
+        live.insert(15, (0, 0));
+        live.insert(14, (5, 90));
+        let report = BacktestReport::new(3, &squad, &live, 50, 0.0);
+        assert_eq!(report.captain.id, 15);
+        // Eight starters on 1, 8 and a defender off the bench for 9 and 15, and 14 doubled
+        assert_eq!(report.points, 8 + 6 + 1 + 5 + 5);
+    }
+
+    #[test]
+    fn test_player_row() {
+        let squad = Squad::with_ids(&(1..=15).collect::<Vec<u16>>());
+        let mut players = squad.players[..2].to_vec();
+        players[1].status = Status::Injured;
+        players[1].news = String::from("Ankle injury - Unknown return date");
+        let rows: Vec<PlayerRow> = players
+            .iter()
+            .map(|p| PlayerRow::new(p, Some(2.5)))
+            .collect();
+        let json: serde_json::Value = serde_json::to_value(&rows).unwrap();
+        assert_eq!(json[1]["id"], 2);
+        assert_eq!(json[1]["status"], "injured");
+        assert_eq!(json[1]["news"], "Ankle injury - Unknown return date");
+        assert_eq!(json[0]["status"], "available");
+        assert!(json[0].get("news").is_none());
+        assert_eq!(rows[1].value, 0.4);
+        assert_eq!(json[1]["total_points"], 0);
+        assert_eq!(json[1]["fixture_difficulty"], 2.5);
+
+        let table = player_table(&rows);
+        assert_eq!(table.lines().count(), 3);
+        assert!(table.lines().nth(2).unwrap().contains("Player2"));
+        assert!(table.lines().nth(2).unwrap().ends_with("injured"));
+    }
+
+    #[test]
+    fn test_simulation_report() {
+        let settings = SimulationSettings {
+            runs: 4,
+            seed: 3,
+            team_correlation: 0.0,
+        };
+        let report = SimulationReport::new(
+            &settings,
+            &[50.0, 40.0, 70.0, 60.0],
+            Some((9, &[45.0, 45.0, 80.0, 60.0])),
+        );
+        assert_eq!(report.mean, 55.0);
+        assert_eq!(report.percentiles[&10], 40.0);
+        assert_eq!(report.percentiles[&50], 50.0);
+        assert_eq!(report.percentiles[&90], 70.0);
+        let rival = report.rival.as_ref().unwrap();
+        assert_eq!(rival.mean, 57.5);
+        assert_eq!(rival.chance_to_beat, 0.375);
+
+        let json: serde_json::Value = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["percentiles"]["25"], 40.0);
+        assert_eq!(json["rival"]["user_id"], 9);
+    }
+}
@@ -0,0 +1,121 @@
+use crate::lineup::{FormationRules, N_POSITIONS};
+use crate::money::Money;
+use serde::{Deserialize, Serialize};
+
+// Names of the rules a profile can pick with `rules = "<name>"`
+pub const PRESETS: [&str; 1] = ["fpl_classic"];
+
+// The rules of a fantasy game the squads are built under. Fields left out of a
+// table are FPL classic's.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameRules {
+    pub squad_size: [usize; N_POSITIONS], // Places for goalkeepers, defenders, midfielders and forwards
+    pub formation: FormationRules,
+    pub max_per_team: usize,
+    pub captain_multiplier: f32,
+    pub budget: Money, // What a new squad can spend
+    pub hit_cost: f32, // Metric each transfer beyond the free ones costs
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        GameRules::fpl_classic()
+    }
+}
+
+impl GameRules {
+    // Fantasy Premier League's classic game
+    pub fn fpl_classic() -> Self {
+        GameRules {
+            squad_size: [2, 5, 5, 3],
+            formation: FormationRules::default(),
+            max_per_team: 3,
+            captain_multiplier: 2.0,
+            budget: Money::from_tenths(1000),
+            hit_cost: 4.0,
+        }
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "fpl_classic" => Some(GameRules::fpl_classic()),
+            _ => None,
+        }
+    }
+
+    pub fn squad_len(&self) -> usize {
+        self.squad_size.iter().sum()
+    }
+
+    // The same rules for a squad with other places, whose lineups can't start
+    // more players than it has
+    pub fn with_squad_size(&self, squad_size: [usize; N_POSITIONS]) -> GameRules {
+        GameRules {
+            squad_size,
+            formation: self.formation.for_capacities(squad_size),
+            ..*self
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.formation.validate()?;
+        if self.formation != self.formation.for_capacities(self.squad_size) {
+            return Err(format!(
+                "a squad of {:?} can't start lineups of {:?}",
+                self.squad_size, self.formation
+            ));
+        }
+        if self.max_per_team == 0 {
+            return Err(String::from("max_per_team must be positive"));
+        }
+        if self.budget <= Money::from_tenths(0) {
+            return Err(format!("budget must be positive, got {:.1}", self.budget));
+        }
+        if self.captain_multiplier < 1.0 || self.hit_cost < 0.0 {
+            return Err(format!(
+                "captain_multiplier must be at least 1 and hit_cost not negative, got {} and {}",
+                self.captain_multiplier, self.hit_cost
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules() {
+        let classic = GameRules::fpl_classic();
+        assert!(classic.validate().is_ok());
+        assert_eq!(classic.squad_len(), 15);
+
+        let small = classic.with_squad_size([1, 2, 2, 1]);
+        assert!(small.validate().is_ok());
+        assert_eq!(small.formation.lineup_size, 6);
+        assert_eq!(small.captain_multiplier, classic.captain_multiplier);
+
+        let too_small = GameRules {
+            squad_size: [1, 2, 2, 1],
+            ..classic
+        };
+        assert!(too_small.validate().is_err());
+        let no_hits = GameRules {
+            hit_cost: -1.0,
+            ..classic
+        };
+        assert!(no_hits.validate().is_err());
+        let broke = GameRules {
+            budget: Money::from_tenths(0),
+            ..classic
+        };
+        assert!(broke.validate().is_err());
+        assert_eq!(GameRules::preset("fpl_classic"), Some(classic));
+        assert_eq!(GameRules::preset("draft"), None);
+
+        let json = serde_json::to_string(&classic).unwrap();
+        assert_eq!(serde_json::from_str::<GameRules>(&json).unwrap(), classic);
+    }
+}
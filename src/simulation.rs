@@ -0,0 +1,321 @@
+use crate::autosub;
+use crate::player::Player;
+use crate::squad::Squad;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::HashMap;
+
+pub const PERCENTILES: [u8; 5] = [10, 25, 50, 75, 90]; // Reported by the simulate command
+const TEAM_STREAM: u64 = 1 << 32; // Team draws use streams past any player id
+const MAX_POINTS: u32 = 100; // Stops the Poisson quantile search for huge metrics
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimulationSettings {
+    pub runs: usize,
+    pub seed: u64,
+    // Chance each player's points follow their team's good or bad day instead of
+    // their own, 0 for independent players
+    pub team_correlation: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Outcome {
+    played: bool,
+    points: f32,
+}
+
+// Smallest number of points a Poisson distribution with mean `mean` reaches `u`
+// of its probability at
+fn poisson_quantile(mean: f64, u: f64) -> u32 {
+    let mut probability = (-mean).exp();
+    let mut cumulative = probability;
+    let mut k = 0;
+    while cumulative < u && k < MAX_POINTS {
+        k += 1;
+        probability *= mean / k as f64;
+        cumulative += probability;
+    }
+    k
+}
+
+// Samples the same gameweeks for every squad, so squads sharing players share
+// their points and comparing squads isn't blurred by sampling noise. Each player
+// plays with their chance of playing and then scores Poisson distributed points
+// around what they're worth when playing, like captaincy assumes. Outcomes are
+// drawn from the player's and team's own streams of the seed, so they don't
+// depend on which other players were simulated first, and are kept by player id
+// until the player's chance or points change.
+pub struct Simulation {
+    settings: SimulationSettings,
+    teams: HashMap<u8, Vec<f64>>,
+    players: HashMap<u16, Sampled>,
+}
+
+struct Sampled {
+    chance: f64,
+    mean: f64,
+    outcomes: Vec<Outcome>,
+}
+
+impl Simulation {
+    pub fn new(settings: SimulationSettings) -> Self {
+        Simulation {
+            settings,
+            teams: HashMap::new(),
+            players: HashMap::new(),
+        }
+    }
+
+    fn rng(&self, stream: u64) -> ChaCha8Rng {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.settings.seed);
+        rng.set_stream(stream);
+        rng
+    }
+    fn sample(&mut self, player: &Player) {
+        let chance = player.chance_of_playing().clamp(0.0, 1.0) as f64;
+        let mean = match chance {
+            c if c > 0.0 => (player.metric().max(0.0) as f64) / c,
+            _ => 0.0,
+        };
+        if let Some(sampled) = self.players.get(&player.id) {
+            if sampled.chance == chance && sampled.mean == mean {
+                return;
+            }
+        }
+        let runs = self.settings.runs;
+        let team = player.team.id();
+        if !self.teams.contains_key(&team) {
+            let mut rng = self.rng(TEAM_STREAM + team as u64);
+            let days = (0..runs).map(|_| rng.gen::<f64>()).collect();
+            self.teams.insert(team, days);
+        }
+        let team_days = &self.teams[&team];
+        let mut rng = self.rng(player.id as u64);
+        let outcomes = (0..runs)
+            .map(|run| {
+                // Every run takes the same draws, whatever the chances
+                let played = rng.gen::<f64>() < chance;
+                let follows_team = rng.gen::<f32>() < self.settings.team_correlation;
+                let own_day = rng.gen::<f64>();
+                let day = if follows_team {
+                    team_days[run]
+                } else {
+                    own_day
+                };
+                Outcome {
+                    played,
+                    points: if played {
+                        poisson_quantile(mean, day) as f32
+                    } else {
+                        0.0
+                    },
+                }
+            })
+            .collect();
+        self.players.insert(
+            player.id,
+            Sampled {
+                chance,
+                mean,
+                outcomes,
+            },
+        );
+    }
+
+    // Points `squad` scores in each run: its best lineup, with the ordered bench
+    // coming on for starters who didn't play and the armband going to the best
+    // captaincy option's captain, or vice-captain when the captain didn't play
+    pub fn points(&mut self, squad: &Squad, captain_upside: f32) -> Vec<f32> {
+        let starters = squad.best_starter_lineup().organized_players();
+        let bench = squad.ordered_bench();
+        for player in starters.iter().chain(&bench) {
+            self.sample(player);
+        }
+        let armband: Vec<u16> =
+            squad
+                .captaincy(captain_upside)
+                .into_iter()
+                .next()
+                .map_or(Vec::new(), |o| {
+                    std::iter::once(o.captain.id)
+                        .chain(o.vice_captain.map(|v| v.id))
+                        .collect()
+                });
+        let extra_armband = squad.rules().captain_multiplier - 1.0;
+        let outcomes = |players: &[Player]| -> Vec<&Vec<Outcome>> {
+            players
+                .iter()
+                .map(|p| &self.players[&p.id].outcomes)
+                .collect()
+        };
+        let (starter_outcomes, bench_outcomes) = (outcomes(&starters), outcomes(&bench));
+        let armband_outcomes: Vec<&Vec<Outcome>> = armband
+            .iter()
+            .map(|id| &self.players[id].outcomes)
+            .collect();
+        let slots = |players: &[Player], outcomes: &[&Vec<Outcome>], run: usize| {
+            players
+                .iter()
+                .zip(outcomes)
+                .map(|(p, o)| (p.position.index(), o[run].played))
+                .collect::<Vec<_>>()
+        };
+        (0..self.settings.runs)
+            .map(|run| {
+                let lineup: f32 = starter_outcomes.iter().map(|o| o[run].points).sum();
+                let subs: f32 = match starter_outcomes.iter().all(|o| o[run].played) {
+                    true => 0.0,
+                    false => autosub::substitute(
+                        &slots(&starters, &starter_outcomes, run),
+                        &slots(&bench, &bench_outcomes, run),
+                        &squad.rules().formation,
+                    )
+                    .iter()
+                    .map(|(_, j)| bench_outcomes[*j][run].points)
+                    .sum(),
+                };
+                let armband = armband_outcomes
+                    .iter()
+                    .find(|o| o[run].played)
+                    .map_or(0.0, |o| o[run].points);
+                lineup + subs + extra_armband * armband
+            })
+            .collect()
+    }
+}
+
+// Summary of simulated points
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution {
+    sorted: Vec<f32>,
+    pub mean: f32,
+    pub stdev: f32,
+}
+
+impl Distribution {
+    pub fn new(points: &[f32]) -> Self {
+        let mut sorted = points.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let n = sorted.len().max(1) as f32;
+        let mean = sorted.iter().sum::<f32>() / n;
+        let variance = sorted.iter().map(|p| (p - mean) * (p - mean)).sum::<f32>() / n;
+        Distribution {
+            sorted,
+            mean,
+            stdev: variance.sqrt(),
+        }
+    }
+    // Points `percent`% of the runs scored at most, 0 without runs
+    pub fn percentile(&self, percent: u8) -> f32 {
+        if self.sorted.is_empty() {
+            return 0.0;
+        }
+        let rank = (percent as f32 / 100.0 * self.sorted.len() as f32).ceil() as usize;
+        self.sorted[rank.clamp(1, self.sorted.len()) - 1]
+    }
+}
+
+// Share of the runs `ours` scored more than `rival`, ties counting as half
+pub fn chance_to_beat(ours: &[f32], rival: &[f32]) -> f32 {
+    let runs = ours.len().min(rival.len());
+    if runs == 0 {
+        return 0.0;
+    }
+    let wins: f32 = ours
+        .iter()
+        .zip(rival)
+        .map(|(a, b)| match a.partial_cmp(b) {
+            Some(std::cmp::Ordering::Greater) => 1.0,
+            Some(std::cmp::Ordering::Equal) => 0.5,
+            _ => 0.0,
+        })
+        .sum();
+    wins / runs as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::Position;
+
+    fn settings(team_correlation: f32) -> SimulationSettings {
+        SimulationSettings {
+            runs: 4000,
+            seed: 7,
+            team_correlation,
+        }
+    }
+
+    #[test]
+    fn test_points() {
+        // Eleven sure starters worth 4 each, the captain doubled
+        let sure = Squad::with_metric(4.0, &[]);
+        let mut simulation = Simulation::new(settings(0.0));
+        let points = simulation.points(&sure, 0.0);
+        assert_eq!(points.len(), 4000);
+        let distribution = Distribution::new(&points);
+        assert!((distribution.mean - 48.0).abs() < 0.5);
+        // Poisson spread of 12 starters' worth of points
+        assert!((distribution.stdev - (11.0 * 4.0 + 3.0 * 4.0f32).sqrt()).abs() < 0.5);
+        assert!(distribution.percentile(10) < distribution.percentile(90));
+
+        // The same seed samples the same gameweeks, another seed doesn't
+        assert_eq!(Simulation::new(settings(0.0)).points(&sure, 0.0), points);
+        let reseeded = SimulationSettings {
+            seed: 8,
+            ..settings(0.0)
+        };
+        assert_ne!(Simulation::new(reseeded).points(&sure, 0.0), points);
+
+        // A captain worth 16 who plays half the time, resampled in place of the sure
+        // midfielder: the vice-captain's 4 and the first bench player's 4 make up
+        // for them when they don't
+        let doubtful =
+            Squad::with_metric(4.0, &[Player::with_chance(8, Position::MID, 3, 16.0, 0.5)]);
+        let mean = Distribution::new(&simulation.points(&doubtful, 0.0)).mean;
+        assert!((mean - (40.0 + 0.5 * 32.0 + 0.5 * (4.0 + 4.0))).abs() < 0.5);
+    }
+
+    #[test]
+    fn test_team_correlation() {
+        // Fully correlated teammates worth the same have the same day every run
+        let mut simulation = Simulation::new(settings(1.0));
+        let (a, b) = (
+            Player::with_chance(1, Position::MID, 1, 5.0, 1.0),
+            Player::with_chance(2, Position::MID, 1, 5.0, 1.0),
+        );
+        simulation.sample(&a);
+        simulation.sample(&b);
+        assert_eq!(
+            simulation.players[&1].outcomes,
+            simulation.players[&2].outcomes
+        );
+        let mut independent = Simulation::new(settings(0.0));
+        independent.sample(&a);
+        independent.sample(&b);
+        assert_ne!(
+            independent.players[&1].outcomes,
+            independent.players[&2].outcomes
+        );
+
+        // Correlated squads spread wider for the same mean
+        let squad = Squad::with_metric(4.0, &[]);
+        let together = Distribution::new(&simulation.points(&squad, 0.0));
+        let apart = Distribution::new(&independent.points(&squad, 0.0));
+        assert!((together.mean - apart.mean).abs() < 1.0);
+        assert!(together.stdev > 1.5 * apart.stdev);
+    }
+
+    #[test]
+    fn test_distribution() {
+        let distribution = Distribution::new(&[4.0, 1.0, 3.0, 2.0]);
+        assert_eq!(distribution.mean, 2.5);
+        assert_eq!(distribution.percentile(25), 1.0);
+        assert_eq!(distribution.percentile(50), 2.0);
+        assert_eq!(distribution.percentile(90), 4.0);
+        assert_eq!(Distribution::new(&[]).percentile(50), 0.0);
+        assert_eq!(chance_to_beat(&[3.0, 1.0, 2.0], &[2.0, 2.0, 2.0]), 0.5);
+        assert_eq!(poisson_quantile(0.0, 0.99), 0);
+        assert_eq!(poisson_quantile(2.0, 0.5), 2);
+    }
+}
@@ -0,0 +1,411 @@
+use crate::api::{self, PlayerResponse};
+use crate::player::{self, Player};
+use crate::squad::Squad;
+use crate::team::Team;
+use serde::{Deserialize, Serialize};
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq)]
+pub struct SourceError(String);
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl Error for SourceError {}
+
+// Strengths are the server's ratings (around 1000 to 1400), 0 when unknown
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TeamInfo {
+    pub id: u8,
+    pub name: String,
+    pub short_name: String,
+    #[serde(default)]
+    pub strength_attack_home: u32,
+    #[serde(default)]
+    pub strength_attack_away: u32,
+    #[serde(default)]
+    pub strength_defence_home: u32,
+    #[serde(default)]
+    pub strength_defence_away: u32,
+}
+
+impl TeamInfo {
+    // From the names the program knows, for sources that only have team ids
+    fn from_team(team: Team) -> Self {
+        let name = team.to_string();
+        TeamInfo {
+            id: team.id(),
+            short_name: name
+                .chars()
+                .filter(|c| c.is_alphanumeric())
+                .take(3)
+                .collect::<String>()
+                .to_uppercase(),
+            name,
+            ..TeamInfo::default()
+        }
+    }
+}
+
+// A match between two teams, with how hard (1 easiest to 5 hardest) it is for each
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Fixture {
+    pub event: Option<u8>, // Gameweek, none until it is scheduled
+    pub team_h: u8,
+    pub team_a: u8,
+    pub team_h_difficulty: u8,
+    pub team_a_difficulty: u8,
+}
+
+// Average difficulty of each team's next `horizon` fixtures, by team id.
+// Fixtures not scheduled in a gameweek yet are skipped.
+pub fn fixture_difficulty(fixtures: &[Fixture], horizon: usize) -> HashMap<u8, f32> {
+    let mut fixtures: Vec<&Fixture> = fixtures.iter().filter(|f| f.event.is_some()).collect();
+    fixtures.sort_by_key(|f| f.event);
+    let mut difficulties: HashMap<u8, Vec<u8>> = HashMap::new();
+    for fixture in &fixtures {
+        for (team, difficulty) in [
+            (fixture.team_h, fixture.team_h_difficulty),
+            (fixture.team_a, fixture.team_a_difficulty),
+        ] {
+            let team_difficulties = difficulties.entry(team).or_default();
+            if team_difficulties.len() < horizon {
+                team_difficulties.push(difficulty);
+            }
+        }
+    }
+    difficulties
+        .into_iter()
+        .filter(|(_, d)| !d.is_empty())
+        .map(|(team, d)| {
+            (
+                team,
+                d.iter().map(|&x| x as f32).sum::<f32>() / d.len() as f32,
+            )
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Gameweek {
+    pub id: u8,
+    #[serde(default)]
+    pub deadline_time: Option<String>,
+    #[serde(default)]
+    pub finished: bool,
+    #[serde(default)]
+    pub is_current: bool,
+    #[serde(default)]
+    pub is_next: bool,
+}
+
+// Where the players and the manager's squad come from, so everything past
+// loading works the same on the live game, a snapshot or a CSV file
+pub trait DataSource {
+    // Highest metric first
+    fn players(&self) -> Result<Vec<Player>, Box<dyn Error>>;
+    fn teams(&self) -> Result<Vec<TeamInfo>, Box<dyn Error>>;
+    // The fixtures still to be played
+    fn fixtures(&self) -> Result<Vec<Fixture>, Box<dyn Error>>;
+    fn gameweeks(&self) -> Result<Vec<Gameweek>, Box<dyn Error>>;
+    // The squad the manager picked in `gameweek`, made of players from `list`
+    fn current_squad(&self, gameweek: u8, list: &[Player]) -> Result<Squad, Box<dyn Error>>;
+    // Minutes the player played in their matches so far, oldest first, or none
+    // when the source doesn't know them
+    fn recent_minutes(&self, _player_id: u16) -> Result<Vec<u32>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+
+    fn fixture_difficulty(&self, horizon: usize) -> Result<HashMap<u8, f32>, Box<dyn Error>> {
+        Ok(fixture_difficulty(&self.fixtures()?, horizon))
+    }
+    fn current_gameweek(&self) -> Option<u8> {
+        self.gameweeks()
+            .ok()?
+            .iter()
+            .find(|g| g.is_current)
+            .map(|g| g.id)
+    }
+}
+
+// The live fantasy server, fetching the bootstrap data once
+pub struct FplApi {
+    user_id: u32,
+    bootstrap: OnceCell<PlayerResponse>,
+}
+
+impl FplApi {
+    pub fn new(user_id: u32) -> Self {
+        FplApi {
+            user_id,
+            bootstrap: OnceCell::new(),
+        }
+    }
+    fn bootstrap(&self) -> Result<&PlayerResponse, Box<dyn Error>> {
+        if let Some(bootstrap) = self.bootstrap.get() {
+            return Ok(bootstrap);
+        }
+        let bootstrap = PlayerResponse::from_json(&api::get_bootstrap_static()?)?;
+        Ok(self.bootstrap.get_or_init(|| bootstrap))
+    }
+}
+
+impl DataSource for FplApi {
+    fn players(&self) -> Result<Vec<Player>, Box<dyn Error>> {
+        Ok(self.bootstrap()?.sorted_players())
+    }
+    fn teams(&self) -> Result<Vec<TeamInfo>, Box<dyn Error>> {
+        Ok(self.bootstrap()?.teams.clone())
+    }
+    fn fixtures(&self) -> Result<Vec<Fixture>, Box<dyn Error>> {
+        api::get_fixtures()
+    }
+    fn gameweeks(&self) -> Result<Vec<Gameweek>, Box<dyn Error>> {
+        Ok(self.bootstrap()?.events.clone())
+    }
+    fn current_squad(&self, gameweek: u8, list: &[Player]) -> Result<Squad, Box<dyn Error>> {
+        api::get_my_squad(self.user_id, gameweek, list)
+    }
+    fn recent_minutes(&self, player_id: u16) -> Result<Vec<u32>, Box<dyn Error>> {
+        api::get_match_minutes(player_id)
+    }
+}
+
+// Bootstrap data saved by the snapshot command. It has no fixtures or squads.
+pub struct Snapshot {
+    path: PathBuf,
+    bootstrap: PlayerResponse,
+}
+
+impl Snapshot {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| SourceError(format!("could not read {}: {}", path.display(), e)))?;
+        Snapshot::from_json(path, &contents)
+    }
+    fn from_json(path: &Path, json: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(Snapshot {
+            path: path.to_path_buf(),
+            bootstrap: PlayerResponse::from_json(json)?,
+        })
+    }
+}
+
+fn missing(path: &Path, what: &str) -> Box<dyn Error> {
+    Box::new(SourceError(format!("{} has no {}", path.display(), what)))
+}
+
+impl DataSource for Snapshot {
+    fn players(&self) -> Result<Vec<Player>, Box<dyn Error>> {
+        Ok(self.bootstrap.sorted_players())
+    }
+    fn teams(&self) -> Result<Vec<TeamInfo>, Box<dyn Error>> {
+        Ok(self.bootstrap.teams.clone())
+    }
+    fn fixtures(&self) -> Result<Vec<Fixture>, Box<dyn Error>> {
+        Err(missing(&self.path, "fixtures"))
+    }
+    fn gameweeks(&self) -> Result<Vec<Gameweek>, Box<dyn Error>> {
+        Ok(self.bootstrap.events.clone())
+    }
+    fn current_squad(&self, _gameweek: u8, _list: &[Player]) -> Result<Squad, Box<dyn Error>> {
+        Err(missing(&self.path, "squad, pass one with --squad-file"))
+    }
+}
+
+// Players written by `players export` to CSV, or any file with the same columns.
+// Teams are the ones the players play for and there are no gameweeks.
+pub struct CsvSource {
+    path: PathBuf,
+    players: Vec<Player>,
+}
+
+impl CsvSource {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let file = fs::File::open(path)
+            .map_err(|e| SourceError(format!("could not read {}: {}", path.display(), e)))?;
+        CsvSource::from_reader(path, file)
+    }
+    fn from_reader<R: std::io::Read>(path: &Path, reader: R) -> Result<Self, Box<dyn Error>> {
+        let mut players = player::read_csv(reader)
+            .map_err(|e| SourceError(format!("error in {}: {}", path.display(), e)))?;
+        players.sort_by(|a, b| b.metric().partial_cmp(&a.metric()).unwrap());
+        Ok(CsvSource {
+            path: path.to_path_buf(),
+            players,
+        })
+    }
+}
+
+impl DataSource for CsvSource {
+    fn players(&self) -> Result<Vec<Player>, Box<dyn Error>> {
+        Ok(self.players.clone())
+    }
+    fn teams(&self) -> Result<Vec<TeamInfo>, Box<dyn Error>> {
+        let mut ids: Vec<u8> = self.players.iter().map(|p| p.team.id()).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        Ok(ids
+            .into_iter()
+            .map(|id| TeamInfo::from_team(Team::new(id)))
+            .collect())
+    }
+    fn fixtures(&self) -> Result<Vec<Fixture>, Box<dyn Error>> {
+        Err(missing(&self.path, "fixtures"))
+    }
+    fn gameweeks(&self) -> Result<Vec<Gameweek>, Box<dyn Error>> {
+        Ok(Vec::new())
+    }
+    fn current_squad(&self, _gameweek: u8, _list: &[Player]) -> Result<Squad, Box<dyn Error>> {
+        Err(missing(&self.path, "squad, pass one with --squad-file"))
+    }
+}
+
+// Files ending in .csv are read as players, anything else as a snapshot
+pub fn from_file(path: &Path) -> Result<Box<dyn DataSource>, Box<dyn Error>> {
+    if path.extension().is_some_and(|e| e == "csv") {
+        Ok(Box::new(CsvSource::load(path)?))
+    } else {
+        Ok(Box::new(Snapshot::load(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+    use crate::player::{Position, Status};
+
+    const BOOTSTRAP: &str = r#"{
+        "elements": [
+            {"chance_of_playing_next_round": null, "form": "4.5", "element_type": 3,
+             "web_name": "Saka", "now_cost": 90, "team": 1, "id": 7, "total_points": 50,
+             "ep_next": "5.1"},
+            {"chance_of_playing_next_round": 50, "form": "6.0", "element_type": 4,
+             "web_name": "Watkins", "now_cost": 85, "team": 2, "id": 9, "total_points": 60,
+             "ep_next": "6.2", "status": "d", "news": "Knock - 50% chance of playing",
+             "news_added": "2024-08-22T15:00:00Z", "selected_by_percent": "31.4"}
+        ],
+        "teams": [
+            {"id": 1, "name": "Arsenal", "short_name": "ARS", "strength": 4,
+             "strength_attack_home": 1300, "strength_attack_away": 1250,
+             "strength_defence_home": 1320, "strength_defence_away": 1280},
+            {"id": 2, "name": "Aston Villa", "short_name": "AVL", "strength": 4}
+        ],
+        "events": [
+            {"id": 1, "deadline_time": "2024-08-16T17:30:00Z", "finished": true,
+             "is_current": false, "is_next": false},
+            {"id": 2, "deadline_time": "2024-08-24T10:00:00Z", "finished": false,
+             "is_current": true, "is_next": false}
+        ]
+    }"#;
+
+    #[test]
+    fn test_fixture_difficulty() {
+        let json = r#"[
+            {"event": 2, "team_h": 1, "team_a": 2, "team_h_difficulty": 2, "team_a_difficulty": 5},
+            {"event": 1, "team_h": 2, "team_a": 1, "team_h_difficulty": 3, "team_a_difficulty": 4},
+            {"event": 3, "team_h": 1, "team_a": 3, "team_h_difficulty": 5, "team_a_difficulty": 2},
+            {"event": null, "team_h": 3, "team_a": 2, "team_h_difficulty": 1, "team_a_difficulty": 1}
+        ]"#;
+        let fixtures: Vec<Fixture> = serde_json::from_str(json).unwrap();
+        let difficulty = fixture_difficulty(&fixtures, 2);
+        assert_eq!(difficulty[&1], 3.0);
+        assert_eq!(difficulty[&2], 4.0);
+        assert_eq!(difficulty[&3], 2.0);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let path = Path::new("before_gw2.json");
+        let snapshot = Snapshot::from_json(path, BOOTSTRAP).unwrap();
+        let players = snapshot.players().unwrap();
+        assert_eq!(players.iter().map(|p| p.id).collect::<Vec<_>>(), vec![9, 7]);
+        assert_eq!(players[0].health, 0.5);
+        assert_eq!(players[0].status, Status::Doubtful);
+        assert_eq!(players[0].news, "Knock - 50% chance of playing");
+        assert_eq!(players[1].status, Status::Available);
+        assert_eq!(players[1].news_added, None);
+        assert_eq!(players[0].ownership(), 0.314);
+        assert_eq!(players[1].selected_by_percent, 0.0);
+        let teams = snapshot.teams().unwrap();
+        assert_eq!(teams[0].strength_defence_away, 1280);
+        assert_eq!(teams[1].short_name, "AVL");
+        assert_eq!(teams[1].strength_attack_home, 0);
+        assert_eq!(snapshot.gameweeks().unwrap().len(), 2);
+        assert_eq!(snapshot.current_gameweek(), Some(2));
+        assert_eq!(
+            snapshot.fixture_difficulty(3).unwrap_err().to_string(),
+            "before_gw2.json has no fixtures"
+        );
+        assert!(snapshot.current_squad(2, &players).is_err());
+
+        // Older snapshots only kept the players
+        let players_only = r#"{"elements": []}"#;
+        let snapshot = Snapshot::from_json(path, players_only).unwrap();
+        assert!(snapshot.teams().unwrap().is_empty());
+        assert_eq!(snapshot.current_gameweek(), None);
+    }
+
+    #[test]
+    fn test_csv_source() {
+        let players = vec![
+            Player::new(
+                1.0,
+                1.0,
+                Money::from_tenths(45),
+                String::from("Raya"),
+                Position::GK,
+                1,
+                Team::new(1),
+                10,
+                3.0,
+            ),
+            Player::new(
+                2.0,
+                1.0,
+                Money::from_tenths(130),
+                String::from("Salah"),
+                Position::MID,
+                2,
+                Team::new(11),
+                90,
+                8.0,
+            ),
+        ];
+        let mut csv = Vec::new();
+        player::write_csv(&players, &mut csv).unwrap();
+        let source = CsvSource::from_reader(Path::new("players.csv"), &csv[..]).unwrap();
+        let read = source.players().unwrap();
+        assert_eq!(read, vec![players[1].clone(), players[0].clone()]);
+        assert_eq!(read[0].price, Money::from_tenths(130));
+        assert_eq!(
+            source.teams().unwrap(),
+            vec![
+                TeamInfo {
+                    id: 1,
+                    name: String::from("Arsenal"),
+                    short_name: String::from("ARS"),
+                    ..TeamInfo::default()
+                },
+                TeamInfo {
+                    id: 11,
+                    name: String::from("Liverpool"),
+                    short_name: String::from("LIV"),
+                    ..TeamInfo::default()
+                },
+            ]
+        );
+        assert_eq!(source.current_gameweek(), None);
+        assert!(source.fixture_difficulty(3).is_err());
+
+        let broken = CsvSource::from_reader(Path::new("players.csv"), &b"name,id\nSalah,x\n"[..]);
+        assert!(broken.is_err());
+    }
+}
@@ -1,28 +1,19 @@
+use crate::autosub;
+use crate::captaincy::{self, CaptainOption, CAPTAINCY_OPTIONS};
+use crate::lineup;
+use crate::minutes::ExpectedMinutes;
+use crate::money::Money;
 use crate::player::{Player, Position};
+use crate::rules::GameRules;
 use crate::team::Team;
-use crate::CAPTAIN_MULTIPLIER;
+use crate::transfer::Transfer;
 extern crate ordered_float;
 use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
 
+use std::convert::TryFrom;
 use std::fmt;
 
-const N_GK: usize = 2;
-const N_DEF: usize = 5;
-const N_MID: usize = 5;
-const N_FWD: usize = 3;
-const EPSILON: f32 = 1e-4;
-const MAX_PLAYERS_PER_TEAM: usize = 3;
-const POSSIBLE_LINEUPS: [&'static [usize; 4]; 8] = [
-    &[1, 3, 4, 3],
-    &[1, 4, 3, 3],
-    &[1, 5, 2, 3],
-    &[1, 3, 5, 2],
-    &[1, 4, 4, 2],
-    &[1, 5, 3, 2],
-    &[1, 5, 4, 1],
-    &[1, 4, 5, 1],
-];
-
 #[derive(Debug, PartialEq)]
 pub enum AddPlayerError {
     TooExpensiveError(String),
@@ -30,10 +21,22 @@ pub enum AddPlayerError {
     TeamsSpotFull(String),
     DuplicatePlayer(String),
 }
+impl fmt::Display for AddPlayerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddPlayerError::TooExpensiveError(reason)
+            | AddPlayerError::PositionFull(reason)
+            | AddPlayerError::TeamsSpotFull(reason)
+            | AddPlayerError::DuplicatePlayer(reason) => write!(f, "{}", reason),
+        }
+    }
+}
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(try_from = "SquadData", into = "SquadData")]
 pub struct Squad {
-    max_cost: f32,
+    max_cost: Money,
+    rules: GameRules,
     goalkeepers: Vec<Player>,
     defenders: Vec<Player>,
     midfielders: Vec<Player>,
@@ -46,8 +49,8 @@ impl fmt::Display for Squad {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Squad with Metric: {:.2}, cost: {:.2}",
-            self.total_metric(CAPTAIN_MULTIPLIER),
+            "Squad with Metric: {:.2}, cost: {}",
+            self.total_metric(self.rules.captain_multiplier),
             self.total_cost()
         )
     }
@@ -59,44 +62,144 @@ impl PartialEq for Squad {
 }
 impl Clone for Squad {
     fn clone(&self) -> Squad {
-        let mut copy = Squad::new(self.max_cost());
+        let mut copy = self.emptied();
         for player in &self.players {
-            copy.force_add_player(&player);
+            copy.force_add_player(player);
         }
         copy
     }
 }
+
+// The players of one position and how many of them the squad takes
+#[derive(Serialize, Deserialize)]
+struct PositionGroup {
+    capacity: usize,
+    players: Vec<Player>,
+}
+
+// Serialized form of a squad. The position vectors are kept in their order and
+// `max_cost` is stored as is, so a squad round-trips unchanged.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SquadData {
+    max_cost: Money,
+    #[serde(default)]
+    rules: Option<GameRules>, // FPL classic rules for the squad's places when missing
+    goalkeepers: PositionGroup,
+    defenders: PositionGroup,
+    midfielders: PositionGroup,
+    forwards: PositionGroup,
+}
+
+impl From<Squad> for SquadData {
+    fn from(squad: Squad) -> Self {
+        let [n_gk, n_def, n_mid, n_fwd] = squad.capacities();
+        let group = |capacity: usize, players: &Vec<Player>| PositionGroup {
+            capacity,
+            players: players.clone(),
+        };
+        SquadData {
+            max_cost: squad.max_cost,
+            rules: Some(squad.rules),
+            goalkeepers: group(n_gk, &squad.goalkeepers),
+            defenders: group(n_def, &squad.defenders),
+            midfielders: group(n_mid, &squad.midfielders),
+            forwards: group(n_fwd, &squad.strikers),
+        }
+    }
+}
+
+impl TryFrom<SquadData> for Squad {
+    type Error = String;
+    // Rebuilt through `try_add_player`, so a hand edited file can't break the squad rules
+    fn try_from(data: SquadData) -> Result<Self, Self::Error> {
+        let capacities = [
+            data.goalkeepers.capacity,
+            data.defenders.capacity,
+            data.midfielders.capacity,
+            data.forwards.capacity,
+        ];
+        let rules = data
+            .rules
+            .unwrap_or_else(|| GameRules::fpl_classic().with_squad_size(capacities));
+        rules.validate()?;
+        if rules.squad_size != capacities {
+            return Err(format!(
+                "the rules are for a squad of {:?} but the squad has {:?} places",
+                rules.squad_size, capacities
+            ));
+        }
+        let mut squad = Squad::with_rules(data.max_cost, rules);
+        let groups = [
+            (Position::GK, data.goalkeepers),
+            (Position::DEF, data.defenders),
+            (Position::MID, data.midfielders),
+            (Position::FWD, data.forwards),
+        ];
+        for (position, group) in groups {
+            for player in &group.players {
+                if player.position != position {
+                    return Err(format!(
+                        "{} is a {} but is listed with the {} players",
+                        player.name, player.position, position
+                    ));
+                }
+                squad.try_add_player(player).map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(squad)
+    }
+}
 impl Squad {
-    pub fn new(max_cost: f32) -> Squad {
-        Squad::new_with_size(max_cost, N_GK, N_DEF, N_MID, N_FWD)
+    pub fn new(max_cost: Money) -> Squad {
+        Squad::with_rules(max_cost, GameRules::fpl_classic())
+    }
+    pub fn with_rules(max_cost: Money, rules: GameRules) -> Squad {
+        let [n_gk, n_def, n_mid, n_fwd] = rules.squad_size;
+        Squad {
+            max_cost,
+            rules,
+            goalkeepers: Vec::with_capacity(n_gk),
+            defenders: Vec::with_capacity(n_def),
+            midfielders: Vec::with_capacity(n_mid),
+            strikers: Vec::with_capacity(n_fwd),
+            players: Vec::with_capacity(rules.squad_len()),
+        }
     }
 
     #[allow(dead_code)]
     pub fn new_with_size(
-        max_cost: f32,
+        max_cost: Money,
         n_gk: usize,
         n_def: usize,
         n_mid: usize,
         n_fwd: usize,
     ) -> Squad {
-        Squad {
-            max_cost: max_cost,
-            goalkeepers: Vec::with_capacity(n_gk),
-            defenders: Vec::with_capacity(n_def),
-            midfielders: Vec::with_capacity(n_mid),
-            strikers: Vec::with_capacity(n_fwd),
-            players: Vec::with_capacity(n_gk + n_def + n_mid + n_fwd),
-        }
+        Squad::with_rules(
+            max_cost,
+            GameRules::fpl_classic().with_squad_size([n_gk, n_def, n_mid, n_fwd]),
+        )
     }
-    pub fn max_cost(&self) -> f32 {
+    // An empty squad with the same budget and rules
+    fn emptied(&self) -> Squad {
+        Squad::with_rules(self.max_cost, self.rules)
+    }
+    pub fn rules(&self) -> &GameRules {
+        &self.rules
+    }
+    pub fn max_cost(&self) -> Money {
         self.max_cost
     }
-    pub fn set_max_cost(&mut self, max_cost: f32) {
-        self.max_cost = max_cost + EPSILON;
+    pub fn set_max_cost(&mut self, max_cost: Money) {
+        self.max_cost = max_cost;
     }
-    pub fn total_cost(&self) -> f32 {
+    pub fn total_cost(&self) -> Money {
         self.players.iter().map(|p| p.price).sum()
     }
+    // Places for goalkeepers, defenders, midfielders and forwards
+    pub fn capacities(&self) -> [usize; 4] {
+        self.rules.squad_size
+    }
 
     pub fn sort_players(&mut self) {
         self.goalkeepers.sort_by(|a, b| {
@@ -122,7 +225,7 @@ impl Squad {
     }
 
     pub fn remove_player(&mut self, player: &Player) {
-        if self.has_player(&player) {
+        if self.has_player(player) {
             match player.position {
                 Position::GK => self.goalkeepers.retain(|p| p != player),
                 Position::DEF => self.defenders.retain(|p| p != player),
@@ -133,30 +236,14 @@ impl Squad {
         }
     }
 
-    pub fn captain(&self) -> Player {
-        self.players
-            .iter()
-            .max_by_key(|p| OrderedFloat(p.metric()))
-            .expect("Failed to sort the metrics for a captain")
-            .clone()
-    }
-
-    pub fn vice_captain(&self) -> Player {
-        let mut copied_players = self.players.clone();
-        copied_players.sort_by(|a, b| {
-            b.metric()
-                .partial_cmp(&a.metric())
-                .expect("Error sorting players")
-        });
-        let mut vice_captain = copied_players.get(1).expect("Can't get second best player").clone();
-        if self.captain() == vice_captain {
-            // Try the first one
-            vice_captain = copied_players.get(0).expect("Can't get best player").clone();
-            if self.captain() == vice_captain {
-                panic!("Error in vice_captain(). Top two players evaluated to captain.");
-            }
-        }
-        vice_captain
+    // Captain and vice-captain pairs from the best lineup, the most expected
+    // armband points (plus `upside` times their spread) first
+    pub fn captaincy(&self, upside: f32) -> Vec<CaptainOption> {
+        captaincy::options(
+            &self.best_starter_lineup().organized_players(),
+            self.rules.captain_multiplier,
+            upside,
+        )
     }
 
     fn players_from_team(&self, team: Team) -> usize {
@@ -168,10 +255,6 @@ impl Squad {
         // self.players.iter().filter(|&p| p == player).count() > 0
     }
 
-    pub fn sort_and_organized_players(&mut self) -> Vec<Player> {
-        self.sort_players();
-        self.organized_players()
-    }
     pub fn organized_players(&self) -> Vec<Player> {
         let mut s_copy = self.clone();
         s_copy.sort_players();
@@ -181,63 +264,136 @@ impl Squad {
         s_copy.goalkeepers
     }
 
-    pub fn changed_squad(&self, current_squad: &Squad) -> String {
-        let mut result: String = String::from("\n\nChanged Squad:\n  Lineup:  ");
+    pub fn changed_squad(&self, current_squad: &Squad, captain_upside: f32) -> String {
+        let mut result: String = String::from("\n\nChanged Squad:\n");
+        result.push_str(&self.lineup_description(captain_upside));
+        result.push_str("\n\n  Transfers needed:\n");
+        let mut changes_needed = self.changes_from(current_squad);
+        if changes_needed.is_empty() {
+            changes_needed = String::from("    None");
+        }
+        result.push_str(&format!("{}\n", changes_needed));
+        result
+    }
+    pub fn lineup_description(&self, captain_upside: f32) -> String {
+        let mut result: String = String::from("  Lineup:  ");
         for player in self.best_starter_lineup().players {
             result.push_str(&format!(" {:?}", player));
         }
         result.push_str("\n  Bench:   ");
-        for player in self.bench().players {
+        for player in self.ordered_bench() {
             result.push_str(&format!(" {:?}", player));
         }
-        result.push_str(&format!(
-            "\n  Captain:  {:?}, metric: {}\n  Vice-Captain:  {:?}, metric: {}",
-            self.captain(),
-            self.captain().metric(),
-            self.vice_captain(),
-            self.vice_captain().metric()
-        ));
-        result.push_str("\n\n  Transfers needed:\n");
-        let mut changes_needed = self.changes_from(current_squad);
-        if changes_needed == String::from(""){
-            changes_needed = String::from("    None");
+        let options = self.captaincy(captain_upside);
+        if let Some(best) = options.first() {
+            result.push_str(&format!(
+                "\n  Captain:  {:?}, metric: {}",
+                best.captain,
+                best.captain.metric()
+            ));
+            if let Some(vice_captain) = &best.vice_captain {
+                result.push_str(&format!(
+                    "\n  Vice-Captain:  {:?}, metric: {}",
+                    vice_captain,
+                    vice_captain.metric()
+                ));
+            }
+            result.push_str("\n  Captaincy:");
+            for option in options.iter().take(CAPTAINCY_OPTIONS) {
+                let vice_captain = option
+                    .vice_captain
+                    .as_ref()
+                    .map_or(String::new(), |v| format!("/{:?}", v));
+                result.push_str(&format!(
+                    " {:?}{} {:.2},",
+                    option.captain, vice_captain, option.expected_points
+                ));
+            }
+            result.pop();
+        }
+        let risks = self.rotation_risks();
+        if !risks.is_empty() {
+            result.push_str("\n  Rotation risks:");
+            for (player, minutes) in risks {
+                result.push_str(&format!(
+                    " {:?} (starts {:.0}%, {:.0} min)",
+                    player,
+                    minutes.start * 100.0,
+                    minutes.minutes
+                ));
+            }
         }
-        result.push_str(&format!("{}\n", changes_needed));
         result
     }
-    pub fn number_of_changes(&self, other: &Squad) -> usize {
-        self.players
+    // Starters in the best lineup who may well not play 60 minutes
+    pub fn rotation_risks(&self) -> Vec<(Player, ExpectedMinutes)> {
+        self.best_starter_lineup()
+            .organized_players()
+            .into_iter()
+            .filter_map(|p| p.expected_minutes.filter(|m| m.risky()).map(|m| (p, m)))
+            .collect()
+    }
+    // Transfers from `current` to this squad, pairing the players sold and bought
+    // in each position from the highest metric down. Positions with a different
+    // number of players only pair up as many as both have.
+    pub fn transfers_from(&self, current: &Squad) -> Vec<Transfer> {
+        let by_metric = |players: &Vec<Player>, other: &Squad| {
+            let mut unique: Vec<Player> = players
+                .iter()
+                .filter(|p| !other.has_player(p))
+                .cloned()
+                .collect();
+            unique.sort_by(|a, b| {
+                b.metric()
+                    .partial_cmp(&a.metric())
+                    .expect("player metrics could not be compared")
+            });
+            unique
+        };
+        let positions = [
+            (&current.goalkeepers, &self.goalkeepers),
+            (&current.defenders, &self.defenders),
+            (&current.midfielders, &self.midfielders),
+            (&current.strikers, &self.strikers),
+        ];
+        positions
             .iter()
-            .filter(|&p| !other.has_player(p))
-            .count()
+            .flat_map(|(current_players, new_players)| {
+                by_metric(current_players, self)
+                    .into_iter()
+                    .zip(by_metric(new_players, current))
+                    .map(|(out, in_)| Transfer { out, in_ })
+            })
+            .collect()
     }
     pub fn changes_from(&self, other: &Squad) -> String {
-        let mut unique_from_self = Squad::new(1000.0);
-        let mut unique_from_other = Squad::new(1000.0);
-        for (my_player, other_player) in self.players.iter().zip(other.players.iter()) {
-            if !other.has_player(my_player) {
-                unique_from_self.force_add_player(my_player);
-            }
-            if !self.has_player(other_player) {
-                unique_from_other.force_add_player(other_player);
-            }
-        }
-        let mut result: String = String::new();
-        for (mine, other) in unique_from_self
-            .sort_and_organized_players()
+        self.transfers_from(other)
             .iter()
-            .zip(unique_from_other.sort_and_organized_players().iter())
-        {
-            assert!(mine.position == other.position);
-            result.push_str(&format!(
-                "    Out: {:?}, {} <-----------> In: {:?}, {}\n",
-                other,
-                other.metric(),
-                mine,
-                mine.metric()
-            ));
+            .map(|t| {
+                format!(
+                    "    Out: {:?}, {} <-----------> In: {:?}, {}\n",
+                    t.out,
+                    t.out.metric(),
+                    t.in_,
+                    t.in_.metric()
+                )
+            })
+            .collect()
+    }
+    // The squad after selling `players_out` and buying `players_in`, with the same budget
+    pub fn after_transfers(
+        &self,
+        players_out: &[Player],
+        players_in: &[Player],
+    ) -> Result<Squad, AddPlayerError> {
+        let mut squad = self.clone();
+        for player in players_out {
+            squad.remove_player(player);
         }
-        result
+        for player in players_in {
+            squad.try_add_player(player)?;
+        }
+        Ok(squad)
     }
     // Return a list of the starters from a given list
     pub fn position_starters(&self, position: Position, n_starters: usize) -> Vec<Player> {
@@ -256,17 +412,14 @@ impl Squad {
         ans[..n_starters].to_vec()
     }
     pub fn positions_full(&self) -> bool {
-        self.players.len()
-            == self.goalkeepers.capacity()
-                + self.defenders.capacity()
-                + self.midfielders.capacity()
-                + self.strikers.capacity()
+        self.players.len() == self.rules.squad_len()
     }
     pub fn total_metric(&self, captain_multiplier: f32) -> f32 {
+        let captain = self.players.iter().max_by_key(|p| OrderedFloat(p.metric()));
         self.players
             .iter()
             .map(|p| {
-                if *p == self.captain() {
+                if Some(p) == captain {
                     captain_multiplier * p.metric()
                 } else {
                     p.metric()
@@ -274,19 +427,35 @@ impl Squad {
             })
             .sum()
     }
+    // `total_metric` with each player's differential metric, the captain still
+    // being the best by metric
+    pub fn differential_metric(&self, captain_multiplier: f32, weight: f32) -> f32 {
+        let captain = self.players.iter().max_by_key(|p| OrderedFloat(p.metric()));
+        self.players
+            .iter()
+            .map(|p| {
+                let value = p.differential_metric(weight);
+                if Some(p) == captain {
+                    captain_multiplier * value
+                } else {
+                    value
+                }
+            })
+            .sum()
+    }
 
     pub fn try_add_player(&mut self, player: &Player) -> Result<(), AddPlayerError> {
         // Check team capacity
-        if self.players_from_team(player.team) >= MAX_PLAYERS_PER_TEAM {
+        if self.players_from_team(player.team) >= self.rules.max_per_team {
             return Err(AddPlayerError::TeamsSpotFull(format!(
                 "Too many players from team: {}. Already have: {}",
-                player.team.to_string(),
+                player.team,
                 self.players_from_team(player.team)
             )));
         }
 
         // Check for duplication
-        if self.has_player(&player) {
+        if self.has_player(player) {
             return Err(AddPlayerError::DuplicatePlayer(format!(
                 "Player {} is already in the squad",
                 player.name
@@ -296,7 +465,7 @@ impl Squad {
         // Check for funds
         if player.price + self.total_cost() > self.max_cost() {
             return Err(AddPlayerError::TooExpensiveError(format!(
-                "Not enough funds to add {} who costs {} and you have {:.2} available.",
+                "Not enough funds to add {} who costs {} and you have {} available.",
                 player.name,
                 player.price,
                 self.max_cost() - self.total_cost()
@@ -304,16 +473,17 @@ impl Squad {
         }
 
         // Check position capacity
-        let player_list = match player.position {
-            Position::GK => &self.goalkeepers,
-            Position::DEF => &self.defenders,
-            Position::MID => &self.midfielders,
-            Position::FWD => &self.strikers,
+        let [n_gk, n_def, n_mid, n_fwd] = self.rules.squad_size;
+        let (player_list, capacity) = match player.position {
+            Position::GK => (&self.goalkeepers, n_gk),
+            Position::DEF => (&self.defenders, n_def),
+            Position::MID => (&self.midfielders, n_mid),
+            Position::FWD => (&self.strikers, n_fwd),
         };
-        if player_list.len() > player_list.capacity() {
+        if player_list.len() > capacity {
             panic!()
         }
-        if player_list.len() == player_list.capacity() {
+        if player_list.len() == capacity {
             return Err(AddPlayerError::PositionFull(format!(
                 "Cannot add {}, as there are too many {}: {}",
                 player.name,
@@ -337,63 +507,120 @@ impl Squad {
     }
 
     pub fn bench(&self) -> Squad {
-        let mut bench = Squad::new(self.max_cost());
+        let mut bench = self.emptied();
         let starters = self.best_starter_lineup();
         self.players
             .iter()
             .filter(|p| !starters.has_player(p))
-            .for_each(|p| bench.force_add_player(&p));
+            .for_each(|p| bench.force_add_player(p));
         bench
     }
+    // The bench in the order that gets the most expected points from auto-subs
+    pub fn ordered_bench(&self) -> Vec<Player> {
+        autosub::best_bench_order(
+            &self.best_starter_lineup().organized_players(),
+            &self.bench().organized_players(),
+            &self.rules.formation,
+        )
+    }
+    // Points the ordered bench is expected to add through auto-subs
+    pub fn auto_sub_points(&self) -> f32 {
+        autosub::expected_sub_points(
+            &self.best_starter_lineup().organized_players(),
+            &self.ordered_bench(),
+            &self.rules.formation,
+        )
+    }
+    // The best lineup the squad's formation rules allow
     pub fn best_starter_lineup(&self) -> Squad {
-        assert_eq!(
-            vec![
-                self.goalkeepers.capacity(),
-                self.defenders.capacity(),
-                self.midfielders.capacity(),
-                self.strikers.capacity()
-            ],
-            vec![N_GK, N_DEF, N_MID, N_FWD]
+        let sorted = [
+            self.position_starters(Position::GK, self.goalkeepers.len()),
+            self.position_starters(Position::DEF, self.defenders.len()),
+            self.position_starters(Position::MID, self.midfielders.len()),
+            self.position_starters(Position::FWD, self.strikers.len()),
+        ];
+        let metrics: Vec<Vec<f32>> = sorted
+            .iter()
+            .map(|players| players.iter().map(Player::metric).collect())
+            .collect();
+        let lineup = lineup::best_lineup(
+            [&metrics[0], &metrics[1], &metrics[2], &metrics[3]],
+            &self.rules.formation,
+            self.rules.captain_multiplier,
         );
-        let mut best_lineup: Option<Squad> = None;
-        let mut best_metric = 0.0;
-        for lineup in &POSSIBLE_LINEUPS {
-            let mut starting_squad = Squad::new(self.max_cost());
-
-            let gks = self.position_starters(Position::GK, lineup[0]);
-            let defs = self.position_starters(Position::DEF, lineup[1]);
-            let mids = self.position_starters(Position::MID, lineup[2]);
-            let fwds = self.position_starters(Position::FWD, lineup[3]);
-
-            gks.iter()
-                .for_each(|gk| starting_squad.force_add_player(&gk));
-            defs.iter()
-                .for_each(|def| starting_squad.force_add_player(&def));
-            mids.iter()
-                .for_each(|mid| starting_squad.force_add_player(&mid));
-            fwds.iter()
-                .for_each(|fwd| starting_squad.force_add_player(&fwd));
-
-            if best_lineup.is_none()
-                || starting_squad.total_metric(CAPTAIN_MULTIPLIER) > best_metric
-            {
-                best_metric = starting_squad.total_metric(CAPTAIN_MULTIPLIER);
-                best_lineup = Some(starting_squad);
-            }
+        let mut starting_squad = self.emptied();
+        for (players, n_starters) in sorted.iter().zip(lineup.starters.iter()) {
+            players[..*n_starters]
+                .iter()
+                .for_each(|p| starting_squad.force_add_player(p));
         }
-        best_lineup.expect("best_lineup failed to be created")
+        starting_squad
+    }
+}
+
+#[cfg(test)]
+impl Squad {
+    // Position of the `i`th player of a 2-5-5-3 squad
+    fn test_position(i: usize) -> Position {
+        match i {
+            0..=1 => Position::GK,
+            2..=6 => Position::DEF,
+            7..=11 => Position::MID,
+            _ => Position::FWD,
+        }
+    }
+
+    // A 2-5-5-3 squad of the players with `ids` in that order, each costing 5.0
+    // and worth their id
+    pub fn with_ids(ids: &[u16]) -> Squad {
+        let mut squad = Squad::new(Money::from_tenths(1000));
+        for (i, id) in ids.iter().enumerate() {
+            let player = Player::new(
+                0.0,
+                1.0,
+                Money::from_tenths(50),
+                format!("Player{}", id),
+                Squad::test_position(i),
+                *id,
+                Team::new((*id / 3) as u8 + 1),
+                0,
+                *id as f32,
+            );
+            squad.try_add_player(&player).unwrap();
+        }
+        squad
+    }
+
+    // A 2-5-5-3 squad of players 1 to 15 from five teams, sure to play and worth
+    // `metric`, but for the ones in `players` who take the place of their id
+    pub fn with_metric(metric: f32, players: &[Player]) -> Squad {
+        let mut squad = Squad::new(Money::from_tenths(1000));
+        for i in 0..15 {
+            let id = i as u16 + 1;
+            let player = players
+                .iter()
+                .find(|p| p.id == id)
+                .cloned()
+                .unwrap_or_else(|| {
+                    Player::with_chance(id, Squad::test_position(i), (i % 5) as u8 + 1, metric, 1.0)
+                });
+            squad.try_add_player(&player).unwrap();
+        }
+        squad
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lineup::FormationRules;
+    use crate::transfer;
 
     fn pablo_player() -> Player {
         Player::new(
             1.0,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Ortiz"),
             Position::MID,
             3,
@@ -407,7 +634,7 @@ mod tests {
         Player::new(
             2.0,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Buffon"),
             Position::GK,
             13,
@@ -420,7 +647,7 @@ mod tests {
         Player::new(
             3.0,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Karius"),
             Position::GK,
             21,
@@ -434,7 +661,7 @@ mod tests {
         Player::new(
             4.0,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Maldini"),
             Position::DEF,
             14,
@@ -447,7 +674,7 @@ mod tests {
         Player::new(
             5.0,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Terry"),
             Position::DEF,
             18,
@@ -460,7 +687,7 @@ mod tests {
         Player::new(
             6.0,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Vidic"),
             Position::DEF,
             19,
@@ -473,7 +700,7 @@ mod tests {
         Player::new(
             7.0,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Johnson"),
             Position::DEF,
             29,
@@ -486,7 +713,7 @@ mod tests {
         Player::new(
             8.0,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Cahill"),
             Position::DEF,
             20,
@@ -500,7 +727,7 @@ mod tests {
         Player::new(
             9.0,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Hazard"),
             Position::MID,
             10,
@@ -513,7 +740,7 @@ mod tests {
         Player::new(
             10.0,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Gerrard"),
             Position::MID,
             11,
@@ -526,7 +753,7 @@ mod tests {
         Player::new(
             11.0,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Scholes"),
             Position::MID,
             12,
@@ -539,7 +766,7 @@ mod tests {
         Player::new(
             12.0,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Lampard"),
             Position::MID,
             1,
@@ -552,7 +779,7 @@ mod tests {
         Player::new(
             13.0,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Fabregas"),
             Position::MID,
             31,
@@ -565,7 +792,7 @@ mod tests {
         Player::new(
             14.0,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Bale"),
             Position::MID,
             22,
@@ -578,7 +805,7 @@ mod tests {
         Player::new(
             15.0,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Drogba"),
             Position::FWD,
             15,
@@ -591,7 +818,7 @@ mod tests {
         Player::new(
             16.0,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Rooney"),
             Position::FWD,
             16,
@@ -604,7 +831,7 @@ mod tests {
         Player::new(
             17.0,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Suarez"),
             Position::FWD,
             17,
@@ -617,7 +844,7 @@ mod tests {
         Player::new(
             17.0,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Adebayor"),
             Position::FWD,
             40,
@@ -627,7 +854,7 @@ mod tests {
         )
     }
     fn full_squad() -> Squad {
-        let mut squad = Squad::new(100.0);
+        let mut squad = Squad::new(Money::from_tenths(1000));
         squad.try_add_player(&maldini_player()).unwrap();
         squad.try_add_player(&karius_player()).unwrap();
         squad.try_add_player(&suarez_player()).unwrap();
@@ -646,7 +873,7 @@ mod tests {
         squad
     }
     fn full_squad_starters() -> Squad {
-        let mut squad = Squad::new(100.0);
+        let mut squad = Squad::new(Money::from_tenths(1000));
         squad.try_add_player(&karius_player()).unwrap();
         squad.try_add_player(&suarez_player()).unwrap();
         squad.try_add_player(&rooney_player()).unwrap();
@@ -661,7 +888,7 @@ mod tests {
         squad
     }
     fn full_squad_bench() -> Squad {
-        let mut squad = Squad::new(100.0);
+        let mut squad = Squad::new(Money::from_tenths(1000));
         squad.try_add_player(&buffon_player()).unwrap();
         squad.try_add_player(&maldini_player()).unwrap();
         squad.try_add_player(&terry_player()).unwrap();
@@ -669,7 +896,7 @@ mod tests {
         squad
     }
     fn six_p_squad() -> Squad {
-        let mut squad = Squad::new(100.0);
+        let mut squad = Squad::new(Money::from_tenths(1000));
         squad.try_add_player(&drogba_player()).unwrap();
         squad.try_add_player(&lampard_player()).unwrap();
         squad.try_add_player(&scholes_player()).unwrap();
@@ -688,15 +915,83 @@ mod tests {
         assert_eq!(full_squad.bench(), expected_bench);
     }
     #[test]
+    fn test_rotation_risks() {
+        let minutes = |start| ExpectedMinutes {
+            minutes: 90.0 * start,
+            start,
+            appears: 1.0,
+        };
+        let mut squad = Squad::new(Money::from_tenths(1000));
+        for mut player in full_squad().players {
+            // A starter and a bench player who may not play 60 minutes
+            if player.name == "Lampard" || player.name == "Gerrard" {
+                player.expected_minutes = Some(minutes(0.5));
+            } else {
+                player.expected_minutes = Some(minutes(0.9));
+            }
+            squad.try_add_player(&player).unwrap();
+        }
+        let risks = squad.rotation_risks();
+        assert_eq!(risks.len(), 1);
+        assert_eq!(risks[0].0, lampard_player());
+        assert!(squad
+            .lineup_description(0.0)
+            .ends_with("Rotation risks: Lampard (starts 50%, 45 min)"));
+        assert!(full_squad().rotation_risks().is_empty());
+    }
+    #[test]
+    fn test_formation() {
+        let mut squad = Squad::new_with_size(Money::from_tenths(1000), 1, 2, 2, 1);
+        for player in [
+            karius_player(),
+            maldini_player(),
+            terry_player(),
+            gerrard_player(),
+            scholes_player(),
+            drogba_player(),
+        ] {
+            squad.try_add_player(&player).unwrap();
+        }
+        assert!(squad.positions_full());
+        // The default rules start the whole of a squad this small
+        assert_eq!(squad.best_starter_lineup(), squad);
+        assert!(squad.bench().players.is_empty());
+
+        let rules = GameRules {
+            formation: FormationRules::new(4, [1, 1, 1, 0], [1, 2, 2, 1]).unwrap(),
+            ..*squad.rules()
+        };
+        squad.rules = rules;
+        let starters = squad.best_starter_lineup();
+        assert_eq!(
+            starters.organized_players(),
+            vec![
+                karius_player(),
+                terry_player(),
+                scholes_player(),
+                drogba_player()
+            ]
+        );
+        assert_eq!(starters.total_metric(2.0), 49.0);
+        assert_eq!(
+            squad.bench().organized_players(),
+            vec![maldini_player(), gerrard_player()]
+        );
+
+        let copy: Squad = serde_json::from_str(&serde_json::to_string(&squad).unwrap()).unwrap();
+        assert_eq!(copy.rules(), &rules);
+        assert_eq!(copy.clone().rules(), &rules);
+    }
+    #[test]
     fn test_total_metric() {
         let six_squad = six_p_squad();
-        assert_eq!(drogba_player(), six_squad.captain());
-        assert_eq!(69.0, six_squad.total_metric(CAPTAIN_MULTIPLIER));
+        assert_eq!(drogba_player(), six_squad.captaincy(0.0)[0].captain);
+        assert_eq!(69.0, six_squad.total_metric(2.0));
     }
     #[test]
     fn test_changes() {
         let full_squad = full_squad();
-        let mut alt_squad = Squad::new(100.0);
+        let mut alt_squad = Squad::new(Money::from_tenths(1000));
         alt_squad.try_add_player(&maldini_player()).unwrap();
         alt_squad.try_add_player(&karius_player()).unwrap();
         alt_squad.try_add_player(&suarez_player()).unwrap();
@@ -712,16 +1007,77 @@ mod tests {
         alt_squad.try_add_player(&vidic_player()).unwrap();
         alt_squad.try_add_player(&johnson_player()).unwrap();
         alt_squad.try_add_player(&rooney_player()).unwrap();
-        assert_eq!(2, alt_squad.number_of_changes(&full_squad));
-        assert_eq!(2, full_squad.number_of_changes(&alt_squad));
+        assert_eq!(2, alt_squad.transfers_from(&full_squad).len());
+        assert_eq!(2, full_squad.transfers_from(&alt_squad).len());
 
         let expected = String::from(
             "    Out: Gerrard, 10 <-----------> In: Ortiz, 1\n    Out: Drogba, 15 <-----------> In: Adebayor, 17\n",
         );
         assert_eq!(expected, alt_squad.changes_from(&full_squad));
-        let expected = String::from("\n\nChanged Squad:\n  Lineup:   Karius Cahill Johnson Vidic Bale Fabregas Lampard Scholes Suarez Adebayor Rooney\n  Bench:    Maldini Buffon Terry Ortiz\n  Captain:  Adebayor, metric: 17\n  Vice-Captain:  Suarez, metric: 17\n\n  Transfers needed:\n    Out: Gerrard, 10 <-----------> In: Ortiz, 1\n    Out: Drogba, 15 <-----------> In: Adebayor, 17\n\n");
+        let expected = String::from("\n\nChanged Squad:\n  Lineup:   Karius Cahill Johnson Vidic Bale Fabregas Lampard Scholes Suarez Adebayor Rooney\n  Bench:    Buffon Terry Maldini Ortiz\n  Captain:  Suarez, metric: 17\n  Vice-Captain:  Adebayor, metric: 17\n  Captaincy: Suarez/Adebayor 34.00, Adebayor/Suarez 34.00, Suarez/Rooney 34.00\n\n  Transfers needed:\n    Out: Gerrard, 10 <-----------> In: Ortiz, 1\n    Out: Drogba, 15 <-----------> In: Adebayor, 17\n\n");
         print!("{}", expected);
-        assert_eq!(expected, alt_squad.changed_squad(&full_squad));
+        assert_eq!(expected, alt_squad.changed_squad(&full_squad, 0.0));
+    }
+    #[test]
+    fn test_transfers_from() {
+        let current = full_squad();
+        // Same players as `alt_squad` in test_changes, added in a different order
+        let mut new = Squad::new(Money::from_tenths(1000));
+        for player in [
+            rooney_player(),
+            adebayor_player(),
+            suarez_player(),
+            lampard_player(),
+            bale_player(),
+            scholes_player(),
+            fabregas_player(),
+            pablo_player(),
+            johnson_player(),
+            vidic_player(),
+            terry_player(),
+            cahill_player(),
+            maldini_player(),
+            buffon_player(),
+            karius_player(),
+        ] {
+            new.try_add_player(&player).unwrap();
+        }
+        let transfers = new.transfers_from(&current);
+        let pairs: Vec<(String, String)> = transfers
+            .iter()
+            .map(|t| (t.out.name.clone(), t.in_.name.clone()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                (String::from("Gerrard"), String::from("Ortiz")),
+                (String::from("Drogba"), String::from("Adebayor")),
+            ]
+        );
+        assert!(transfers.iter().all(|t| t.out.position == t.in_.position));
+        assert_eq!(
+            transfer::bank_delta(&transfers),
+            gerrard_player().price + drogba_player().price
+                - pablo_player().price
+                - adebayor_player().price
+        );
+        assert_eq!(transfer::hits(&transfers, 1), 1);
+        assert!(current.transfers_from(&current).is_empty());
+
+        let back = current.transfers_from(&new);
+        assert_eq!(back.len(), 2);
+        assert_eq!(back[0].in_.name, "Gerrard");
+
+        let after = current
+            .after_transfers(
+                &[drogba_player(), gerrard_player()],
+                &[adebayor_player(), pablo_player()],
+            )
+            .unwrap();
+        assert!(after.transfers_from(&new).is_empty());
+        assert!(current
+            .after_transfers(&[drogba_player()], &[pablo_player()])
+            .is_err());
     }
     #[test]
     fn test_copy() {
@@ -730,8 +1086,29 @@ mod tests {
         assert_eq!(squad, s_copy);
     }
     #[test]
+    fn test_serialization() {
+        let mut squad = full_squad();
+        squad.set_max_cost(Money::from_tenths(1015));
+        let json = serde_json::to_string(&squad).unwrap();
+        let copy: Squad = serde_json::from_str(&json).unwrap();
+        assert_eq!(copy, squad);
+        assert_eq!(copy.max_cost(), squad.max_cost());
+        assert_eq!(copy.capacities(), [2, 5, 5, 3]);
+        assert_eq!(copy.strikers, squad.strikers);
+        assert!(copy.positions_full());
+
+        let small = Squad::new_with_size(Money::from_tenths(100), 1, 1, 0, 0);
+        let copy: Squad = serde_json::from_str(&serde_json::to_string(&small).unwrap()).unwrap();
+        assert_eq!(copy.capacities(), [1, 1, 0, 0]);
+
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value["goalkeepers"]["players"][0]["position"] = serde_json::json!("DEF");
+        let error = serde_json::from_value::<Squad>(value).unwrap_err();
+        assert!(error.to_string().contains("is listed with the GK players"));
+    }
+    #[test]
     fn test_organized_players() {
-        let mut squad = Squad::new(100.0);
+        let mut squad = Squad::new(Money::from_tenths(1000));
         squad.try_add_player(&drogba_player()).unwrap();
         squad.try_add_player(&lampard_player()).unwrap();
         squad.try_add_player(&scholes_player()).unwrap();
@@ -750,7 +1127,7 @@ mod tests {
     }
     #[test]
     fn test_mid_starters() {
-        let mut squad = Squad::new(100.0);
+        let mut squad = Squad::new(Money::from_tenths(1000));
         squad.try_add_player(&lampard_player()).unwrap();
         squad.try_add_player(&gerrard_player()).unwrap();
         squad.try_add_player(&hazard_player()).unwrap();
@@ -760,33 +1137,33 @@ mod tests {
     }
     #[test]
     fn test_captain() {
-        let mut squad = Squad::new(100.0);
+        let mut squad = Squad::new(Money::from_tenths(1000));
         squad.try_add_player(&drogba_player()).unwrap();
         squad.try_add_player(&lampard_player()).unwrap();
         let player = pablo_player();
         squad.force_add_player(&player);
-        assert_eq!(squad.vice_captain(), lampard_player());
+        assert_eq!(squad.captaincy(0.0)[0].vice_captain, Some(lampard_player()));
     }
     #[test]
     fn test_vice_captain() {
-        let mut squad = Squad::new(100.0);
+        let mut squad = Squad::new(Money::from_tenths(1000));
         let player = lampard_player();
         squad.force_add_player(&player);
         let player = pablo_player();
         squad.force_add_player(&player);
-        assert_eq!(squad.captain(), lampard_player());
+        assert_eq!(squad.captaincy(0.0)[0].captain, lampard_player());
     }
     #[test]
     fn test_max_cost() {
-        let mut squad = Squad::new(100.0);
-        assert_eq!(100.0, squad.max_cost());
-        squad.set_max_cost(95.0);
-        assert_eq!(95.0 + EPSILON, squad.max_cost());
+        let mut squad = Squad::new(Money::from_tenths(1000));
+        assert_eq!(Money::from_tenths(1000), squad.max_cost());
+        squad.set_max_cost(Money::from_tenths(955));
+        assert_eq!(Money::from_tenths(955), squad.max_cost());
     }
 
     #[test]
     fn test_remove_player() {
-        let mut squad = Squad::new(100.0);
+        let mut squad = Squad::new(Money::from_tenths(1000));
         let player = lampard_player();
         squad.try_add_player(&player).unwrap();
         let player = pablo_player();
@@ -796,7 +1173,7 @@ mod tests {
         let player = Player::new(
             7.2,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Lampard"),
             Position::MID,
             1,
@@ -809,7 +1186,7 @@ mod tests {
     }
     #[test]
     fn test_team_full() {
-        let mut squad = Squad::new(100.0);
+        let mut squad = Squad::new(Money::from_tenths(1000));
         let player = lampard_player();
         squad.force_add_player(&player);
         assert!(matches!(
@@ -829,7 +1206,7 @@ mod tests {
     }
     #[test]
     fn test_position_capacity() {
-        let mut squad = Squad::new(100.0);
+        let mut squad = Squad::new(Money::from_tenths(1000));
         let player = lampard_player();
         squad.force_add_player(&player);
         squad.force_add_player(&player);
@@ -838,7 +1215,7 @@ mod tests {
         let player = Player::new(
             7.2,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Ortiz"),
             Position::MID,
             3,
@@ -851,7 +1228,7 @@ mod tests {
         let player = Player::new(
             7.2,
             1.0,
-            0.1,
+            Money::from_tenths(1),
             String::from("Sibley"),
             Position::MID,
             5,
@@ -866,7 +1243,7 @@ mod tests {
     }
     #[test]
     fn test_funds() {
-        let mut squad = Squad::new(1.1);
+        let mut squad = Squad::new(Money::from_tenths(11));
         let player = lampard_player();
         squad.force_add_player(&player);
 
@@ -879,7 +1256,7 @@ mod tests {
         let player = Player::new(
             7.2,
             1.0,
-            0.1,
+            Money::from_tenths(1),
             String::from("Sibley"),
             Position::MID,
             5,
@@ -899,21 +1276,21 @@ mod tests {
     }
     #[test]
     fn test_total_cost() {
-        let mut squad = Squad::new(100.0);
+        let mut squad = Squad::new(Money::from_tenths(1000));
         let player = lampard_player();
         squad.force_add_player(&player);
-        assert_eq!(squad.total_cost(), 1.0);
+        assert_eq!(squad.total_cost(), Money::from_tenths(10));
         squad.force_add_player(&player);
-        assert_eq!(squad.total_cost(), CAPTAIN_MULTIPLIER);
+        assert_eq!(squad.total_cost(), Money::from_tenths(20));
     }
     #[test]
     fn test_players_from_team() {
-        let mut squad = Squad::new(100.0);
+        let mut squad = Squad::new(Money::from_tenths(1000));
         let player = lampard_player();
         let player2 = Player::new(
             7.2,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Ortiz"),
             Position::MID,
             2,
@@ -924,7 +1301,7 @@ mod tests {
         let player3 = Player::new(
             7.2,
             1.0,
-            1.0,
+            Money::from_tenths(10),
             String::from("Sibley"),
             Position::MID,
             3,
@@ -0,0 +1,309 @@
+use crate::money::Money;
+use crate::player::{Player, Position};
+use crate::rules::GameRules;
+use crate::squad::Squad;
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+// Names at least this similar (Jaro-Winkler) to the one in the file are candidates
+const MIN_SIMILARITY: f64 = 0.85;
+const N_SUGGESTIONS: usize = 3;
+
+#[derive(Debug, PartialEq)]
+pub struct SquadFileError(String);
+impl fmt::Display for SquadFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error in squad file: {}", self.0)
+    }
+}
+impl Error for SquadFileError {}
+
+// A player of the file, either an `id` or a `name` optionally narrowed down by
+// `team` (id or part of the name) and `position`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Entry {
+    pub id: Option<u16>,
+    pub name: Option<String>,
+    pub team: Option<String>,
+    pub position: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SquadFile {
+    #[serde(default)]
+    bank: Money,
+    players: Vec<Entry>,
+}
+
+// Lowercase and without accents, so "Martínez" and "martinez" are the same name
+fn normalize(name: &str) -> String {
+    name.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect::<String>()
+        .to_lowercase()
+}
+
+fn describe(players: &[&Player]) -> String {
+    players
+        .iter()
+        .map(|p| format!("{} ({}, {}, id {})", p.name, p.team, p.position, p.id))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Players whose name is the most similar to `name`, all of them on ties
+fn closest<'a>(name: &str, players: &[&'a Player], min_similarity: f64) -> Vec<&'a Player> {
+    let mut scored: Vec<(f64, &Player)> = players
+        .iter()
+        .map(|p| (strsim::jaro_winkler(name, &normalize(&p.name)), *p))
+        .filter(|(score, _)| *score >= min_similarity)
+        .collect();
+    scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    match scored.first() {
+        Some((best, _)) if min_similarity > 0.0 => {
+            let best = *best;
+            scored
+                .into_iter()
+                .filter(|(score, _)| *score == best)
+                .map(|(_, p)| p)
+                .collect()
+        }
+        _ => scored.into_iter().map(|(_, p)| p).collect(),
+    }
+}
+
+impl Entry {
+    // Exact names first, then names containing it as a word (`Fernandes` for
+    // `B.Fernandes`), then the closest names with a typo
+    pub fn find<'a>(&self, list: &'a [Player]) -> Result<&'a Player, String> {
+        let name = match (self.id, &self.name) {
+            (Some(id), _) => {
+                return list
+                    .iter()
+                    .find(|p| p.id == id)
+                    .ok_or_else(|| format!("no player with id {}", id))
+            }
+            (None, Some(name)) => name,
+            (None, None) => return Err(String::from("every player needs a name or an id")),
+        };
+        let position = self
+            .position
+            .as_deref()
+            .map(str::parse::<Position>)
+            .transpose()?;
+        let candidates: Vec<&Player> = list
+            .iter()
+            .filter(|p| self.team.as_deref().is_none_or(|t| p.team.matches(t)))
+            .filter(|p| position.as_ref().is_none_or(|pos| p.position == *pos))
+            .collect();
+
+        let wanted = normalize(name);
+        let exact: Vec<&Player> = candidates
+            .iter()
+            .filter(|p| normalize(&p.name) == wanted)
+            .cloned()
+            .collect();
+        let word: Vec<&Player> = candidates
+            .iter()
+            .filter(|p| {
+                normalize(&p.name)
+                    .split(|c: char| !c.is_alphanumeric())
+                    .any(|w| w == wanted)
+            })
+            .cloned()
+            .collect();
+        let matches = if !exact.is_empty() {
+            exact
+        } else if !word.is_empty() {
+            word
+        } else {
+            closest(&wanted, &candidates, MIN_SIMILARITY)
+        };
+
+        match matches.len() {
+            1 => Ok(matches[0]),
+            0 => {
+                let everyone: Vec<&Player> = list.iter().collect();
+                let suggestions = closest(&wanted, &everyone, 0.0);
+                Err(format!(
+                    "no player matches `{}`, closest names: {}",
+                    name,
+                    describe(&suggestions[..N_SUGGESTIONS.min(suggestions.len())])
+                ))
+            }
+            _ => Err(format!(
+                "`{}` is ambiguous, add a team or position to pick one of: {}",
+                name,
+                describe(&matches)
+            )),
+        }
+    }
+}
+
+// Builds the full squad described by a squad file under `rules`, its budget is
+// the players' cost plus the bank. A file without players is a fresh squad with
+// the rules' whole budget to spend.
+pub fn parse_squad(
+    contents: &str,
+    list: &[Player],
+    rules: GameRules,
+) -> Result<Squad, SquadFileError> {
+    let file: SquadFile = toml::from_str(contents).map_err(|e| SquadFileError(e.to_string()))?;
+    if file.players.is_empty() {
+        return Ok(Squad::with_rules(rules.budget, rules));
+    }
+    let mut squad = Squad::with_rules(Money::UNLIMITED, rules);
+    for entry in &file.players {
+        squad
+            .try_add_player(entry.find(list).map_err(SquadFileError)?)
+            .map_err(|e| SquadFileError(e.to_string()))?;
+    }
+    if !squad.positions_full() {
+        return Err(SquadFileError(format!(
+            "the squad has {} players, it needs a full squad",
+            squad.players.len()
+        )));
+    }
+    squad.sort_players();
+    squad.set_max_cost(file.bank + squad.total_cost());
+    Ok(squad)
+}
+
+// A squad saved by `squad export` with its players' data brought up to date,
+// keeping the money it had in the bank
+pub fn refresh_squad(saved: &Squad, list: &[Player]) -> Result<Squad, SquadFileError> {
+    let bank = saved.max_cost() - saved.total_cost();
+    let mut squad = saved.clone();
+    squad.set_max_cost(Money::UNLIMITED);
+    for player in &saved.players {
+        let current = list
+            .iter()
+            .find(|p| p.id == player.id)
+            .ok_or_else(|| SquadFileError(format!("{} is no longer in the game", player.name)))?;
+        squad.remove_player(player);
+        squad
+            .try_add_player(current)
+            .map_err(|e| SquadFileError(e.to_string()))?;
+    }
+    squad.set_max_cost(bank + squad.total_cost());
+    Ok(squad)
+}
+
+// TOML squad files, or JSON squads saved by `squad export` when the file ends in
+// .json, which keep the rules they were saved with
+pub fn load_squad(path: &Path, list: &[Player], rules: GameRules) -> Result<Squad, Box<dyn Error>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| SquadFileError(format!("could not read {}: {}", path.display(), e)))?;
+    if path.extension().is_some_and(|e| e == "json") {
+        let saved: Squad =
+            serde_json::from_str(&contents).map_err(|e| SquadFileError(e.to_string()))?;
+        return Ok(refresh_squad(&saved, list)?);
+    }
+    Ok(parse_squad(&contents, list, rules)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player::named_players;
+
+    fn find(entry: &str) -> Result<u16, String> {
+        let entry: Entry = toml::from_str(entry).unwrap();
+        entry.find(&named_players()).map(|p| p.id)
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("Martínez"), "martinez");
+        assert_eq!(normalize("Sánchez"), "sanchez");
+        assert_eq!(normalize("Ward-Prowse"), "ward-prowse");
+    }
+
+    #[test]
+    fn test_find() {
+        assert_eq!(find("id = 4"), Ok(4));
+        assert_eq!(find("name = \"martinez\"\nteam = \"villa\""), Ok(1));
+        assert_eq!(find("name = \"Martinez\"\nposition = \"DEF\""), Ok(2));
+        assert_eq!(find("name = \"Fernandes\""), Ok(5));
+        assert_eq!(find("name = \"Robertsen\""), Ok(4));
+        assert_eq!(find("name = \"ward prowse\""), Ok(6));
+
+        let ambiguous = find("name = \"Martinez\"").unwrap_err().to_string();
+        assert!(ambiguous.contains("is ambiguous"));
+        assert!(ambiguous.contains("Martínez (Aston Villa, GK, id 1)"));
+        assert!(ambiguous.contains("Martinez (Man Utd, DEF, id 2)"));
+
+        let missing = find("name = \"Haaland\"").unwrap_err().to_string();
+        assert!(missing.contains("no player matches `Haaland`, closest names:"));
+        assert!(find("id = 99").is_err());
+        assert!(find("team = \"villa\"").is_err());
+    }
+
+    #[test]
+    fn test_parse_squad() {
+        let mut list = Vec::new();
+        let positions = [
+            (Position::GK, 2),
+            (Position::DEF, 5),
+            (Position::MID, 5),
+            (Position::FWD, 3),
+        ];
+        let mut id = 1;
+        for (position, n) in positions.iter() {
+            for _ in 0..*n {
+                list.push(Player::named(
+                    id,
+                    &format!("Player{}", id),
+                    position.clone(),
+                    id as u8,
+                ));
+                id += 1;
+            }
+        }
+        let mut contents = String::from("bank = 1.5\n");
+        for p in &list {
+            contents += &format!("[[players]]\nname = \"{}\"\n", p.name);
+        }
+        let squad = parse_squad(&contents, &list, GameRules::fpl_classic()).unwrap();
+        assert!(squad.positions_full());
+        assert_eq!(
+            squad.max_cost() - squad.total_cost(),
+            Money::from_tenths(15)
+        );
+
+        let short = contents.replace("[[players]]\nname = \"Player15\"\n", "");
+        assert!(parse_squad(&short, &list, GameRules::fpl_classic())
+            .unwrap_err()
+            .to_string()
+            .contains("has 14 players"));
+        let duplicate = contents.replace("Player15", "Player14");
+        assert!(parse_squad(&duplicate, &list, GameRules::fpl_classic()).is_err());
+
+        // No players starts a fresh squad with the whole budget
+        let fresh = parse_squad("players = []", &list, GameRules::fpl_classic()).unwrap();
+        assert!(fresh.players.is_empty());
+        assert_eq!(fresh.max_cost(), GameRules::fpl_classic().budget);
+
+        // An exported squad picks up the new prices and keeps its bank
+        let saved: Squad = serde_json::from_str(&serde_json::to_string(&squad).unwrap()).unwrap();
+        list[0].price = Money::from_tenths(60);
+        let refreshed = refresh_squad(&saved, &list).unwrap();
+        assert_eq!(refreshed, squad);
+        assert_eq!(
+            refreshed.total_cost() - squad.total_cost(),
+            Money::from_tenths(10)
+        );
+        assert_eq!(
+            refreshed.max_cost() - refreshed.total_cost(),
+            Money::from_tenths(15)
+        );
+        assert!(refresh_squad(&saved, &list[1..]).is_err());
+    }
+}
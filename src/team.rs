@@ -1,18 +1,34 @@
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
-#[derive(PartialEq, Clone, Copy)]
+// Serialized as the team id
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct Team {
     idx: u8,
 }
 
 impl Team {
     pub fn new(idx: u8) -> Team {
-        Team { idx: idx }
+        Team { idx }
+    }
+    pub fn id(&self) -> u8 {
+        self.idx
+    }
+    // `query` is a team id or a case insensitive part of the team's name
+    pub fn matches(&self, query: &str) -> bool {
+        match query.trim().parse::<u8>() {
+            Ok(id) => id == self.idx,
+            Err(_) => self
+                .to_string()
+                .to_lowercase()
+                .contains(&query.trim().to_lowercase()),
+        }
     }
 }
 
 impl Team {
-    fn to_string_helper(&self) -> String {
+    fn to_string_helper(self) -> String {
         match self.idx {
             1 => String::from("Arsenal"),
             2 => String::from("Aston Villa"),
@@ -59,4 +75,14 @@ mod tests {
         assert_eq!(team.to_string(), "Burnley");
         assert_eq!(Team::new(1).to_string(), "Arsenal");
     }
+
+    #[test]
+    fn test_matches() {
+        let team = Team::new(12);
+        assert!(team.matches("12"));
+        assert!(team.matches("man city"));
+        assert!(team.matches("City"));
+        assert!(!team.matches("utd"));
+        assert!(!team.matches("13"));
+    }
 }
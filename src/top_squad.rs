@@ -1,31 +1,99 @@
+use crate::compact_squad::{CompactSquad, PlayerPool};
 use crate::key_poller::KeyPoller;
 use crate::key_poller::Keycode;
+use crate::money::Money;
 use crate::optimizer::Listener;
+use crate::report::{OutputFormat, Recommendation, SearchStats};
+use crate::simulation::{Distribution, Simulation};
 use crate::Config;
 use crate::Squad;
-use crate::CAPTAIN_MULTIPLIER;
+use serde::Deserialize;
+use std::fmt;
+use std::str::FromStr;
+
+// What squads are ranked by, less the hits they take
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchObjective {
+    #[default]
+    Metric, // The best lineup's metric, captain included
+    Simulated,    // The mean points of simulated gameweeks, auto-subs included
+    Risk,         // The simulated mean less risk_aversion times the stdev
+    Differential, // The best lineup's metric less what the owners of its players also get
+}
+
+impl SearchObjective {
+    fn simulates(&self) -> bool {
+        matches!(self, SearchObjective::Simulated | SearchObjective::Risk)
+    }
+}
+
+impl fmt::Display for SearchObjective {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SearchObjective::Metric => write!(f, "metric"),
+            SearchObjective::Simulated => write!(f, "simulated"),
+            SearchObjective::Risk => write!(f, "risk"),
+            SearchObjective::Differential => write!(f, "differential"),
+        }
+    }
+}
+
+impl FromStr for SearchObjective {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "metric" => Ok(SearchObjective::Metric),
+            "simulated" => Ok(SearchObjective::Simulated),
+            "risk" => Ok(SearchObjective::Risk),
+            "differential" => Ok(SearchObjective::Differential),
+            _ => Err(format!(
+                "unknown objective `{}`, expected one of: metric, simulated, risk, differential",
+                s
+            )),
+        }
+    }
+}
 
 pub struct TopSquad {
     n_squads: usize,
     top_squad: Squad,
+    top_cost: Money,
+    top_sub_points: f32,
     key_poller: KeyPoller,
     current_squad: Squad,
     config: Config,
     top_adjusted_metric: f32,
     n_tries_for_top: usize,
+    simulation: Option<Simulation>, // With the simulated and risk objectives
+    top_lineup_metric: f32, // Best lineup metric less hits seen, to leave out squads far behind it
 }
 impl TopSquad {
     pub fn new(current_squad: Squad, config: Config) -> Self {
+        let simulation = match config.objective.simulates() {
+            true => Some(Simulation::new(config.simulation)),
+            false => None,
+        };
         let mut squad = TopSquad {
             n_squads: 0,
             key_poller: KeyPoller::new(Keycode::P),
             top_squad: current_squad.clone(),
-            current_squad: current_squad,
-            config: config,
+            top_cost: current_squad.total_cost(),
+            top_sub_points: current_squad.auto_sub_points(),
+            current_squad,
+            config,
             top_adjusted_metric: 0.0,
             n_tries_for_top: 0,
+            simulation,
+            top_lineup_metric: 0.0,
         };
-        squad.top_adjusted_metric = squad.adjusted_metric(&squad.current_squad);
+        // Keeping the current squad takes no transfers
+        squad.top_lineup_metric = squad
+            .current_squad
+            .best_starter_lineup()
+            .total_metric(squad.current_squad.rules().captain_multiplier);
+        let current_squad = squad.current_squad.clone();
+        squad.top_adjusted_metric = squad.value_of(&current_squad);
         squad
     }
     pub fn n_squads_checked(&self) -> usize {
@@ -34,47 +102,123 @@ impl TopSquad {
     pub fn top_squad_idx(&self) -> usize {
         self.n_tries_for_top
     }
-    fn adjusted_metric(&self, squad: &Squad) -> f32 {
-        let n_changes = squad.number_of_changes(&self.current_squad);
-        let max_n = (0 as f32).max(
+    fn hit_cost(&self, n_changes: usize) -> f32 {
+        (0 as f32).max(
             (n_changes as i32 - self.config.free_transfers as i32) as f32
-                * self.config.transfer_cost,
-        );
-        squad.best_starter_lineup().total_metric(CAPTAIN_MULTIPLIER) - max_n
+                * self.config.rules.hit_cost,
+        )
+    }
+    fn simulate(&mut self, squad: &Squad) -> Option<Distribution> {
+        let upside = self.config.captain_upside;
+        self.simulation
+            .as_mut()
+            .map(|simulation| Distribution::new(&simulation.points(squad, upside)))
     }
-    fn set_top_squad(&mut self, squad: &Squad) {
-        self.top_squad = squad.clone();
-        self.top_adjusted_metric = self.adjusted_metric(&self.top_squad);
+    // What the objective makes of a squad before hits
+    fn value_of(&mut self, squad: &Squad) -> f32 {
+        let lineup = squad.best_starter_lineup();
+        let captain_multiplier = squad.rules().captain_multiplier;
+        let risk_aversion = self.config.risk_aversion;
+        match (self.config.objective, self.simulate(squad)) {
+            (SearchObjective::Simulated, Some(points)) => points.mean,
+            (SearchObjective::Risk, Some(points)) => points.mean - risk_aversion * points.stdev,
+            (SearchObjective::Differential, _) => {
+                lineup.differential_metric(captain_multiplier, self.config.ownership_weight)
+            }
+            _ => lineup.total_metric(captain_multiplier),
+        }
+    }
+    // What `squad` is ranked by, less hits. Squads too far behind on lineup metric
+    // to be worth simulating give None.
+    fn adjusted_metric(&mut self, pool: &PlayerPool, squad: &CompactSquad) -> Option<f32> {
+        let hit_cost = self.hit_cost(squad.number_of_changes());
+        let metric = squad.lineup_metric() - hit_cost;
+        match self.config.objective {
+            SearchObjective::Metric => Some(metric),
+            SearchObjective::Differential => {
+                Some(squad.differential_metric(pool, self.config.ownership_weight) - hit_cost)
+            }
+            SearchObjective::Simulated | SearchObjective::Risk => {
+                // Squads further behind than the margin aren't worth simulating
+                if metric + self.config.simulation_margin < self.top_lineup_metric {
+                    return None;
+                }
+                self.top_lineup_metric = self.top_lineup_metric.max(metric);
+                Some(self.value_of(&squad.to_squad(pool)) - hit_cost)
+            }
+        }
+    }
+    // Only the top squads are turned into a `Squad`
+    fn set_top_squad(&mut self, pool: &PlayerPool, squad: &CompactSquad, objective: f32) {
+        self.top_squad = squad.to_squad(pool);
+        self.top_cost = squad.total_cost();
+        self.top_sub_points = self.top_squad.auto_sub_points();
+        self.top_adjusted_metric = objective;
         self.n_tries_for_top = self.n_squads;
     }
+    pub fn top_squad(&self) -> &Squad {
+        &self.top_squad
+    }
     pub fn changes_for_top(&self) -> String {
-        self.top_squad.changed_squad(&self.current_squad)
+        self.top_squad
+            .changed_squad(&self.current_squad, self.config.captain_upside)
+    }
+    pub fn recommendation(&mut self, pool_size: usize) -> Recommendation {
+        let mut recommendation = Recommendation::new(
+            &self.top_squad,
+            &self.current_squad,
+            self.config.free_transfers,
+            self.config.rules.hit_cost,
+            self.config.captain_upside,
+            SearchStats {
+                pool_size,
+                squads_checked: self.n_squads,
+                top_found_after: self.n_tries_for_top,
+            },
+        );
+        let top_squad = self.top_squad.clone();
+        if let Some(points) = self.simulate(&top_squad) {
+            recommendation.objective.simulated_points = Some(points.mean);
+            recommendation.objective.simulated_stdev = Some(points.stdev);
+        }
+        if self.config.objective != SearchObjective::Metric {
+            recommendation.objective.total = self.top_adjusted_metric;
+        }
+        recommendation
+    }
+    // Progress goes to stdout only when it isn't reserved for machine readable output
+    fn verbose(&self) -> bool {
+        self.config.output == OutputFormat::Text
     }
 }
 impl Listener for TopSquad {
-    fn notify_new_squad(&mut self, squad: &Squad) {
+    fn notify_new_squad(&mut self, pool: &PlayerPool, squad: &CompactSquad) {
         self.n_squads += 1;
-        let squad_adjusted_metric = self.adjusted_metric(squad);
+        // Squads left out never beat the top one
+        let squad_adjusted_metric = self
+            .adjusted_metric(pool, squad)
+            .unwrap_or(f32::NEG_INFINITY);
         if squad_adjusted_metric > self.top_adjusted_metric {
-            self.set_top_squad(&squad);
-            println!(
-                "Found a squad with better metric! Squad #: {}, New metric: {:.2}{: <3$}\n",
-                self.n_squads, self.top_adjusted_metric, "", 60
-            );
+            self.set_top_squad(pool, squad, squad_adjusted_metric);
+            if self.verbose() {
+                println!(
+                    "Found a squad with better metric! Squad #: {}, New metric: {:.2}{: <3$}\n",
+                    self.n_squads, self.top_adjusted_metric, "", 60
+                );
+            }
         } else if squad_adjusted_metric == self.top_adjusted_metric {
             let bench_points_required_for_change =
-                (squad.total_cost() - self.top_squad.total_cost()) * self.config.bench_point_value; // If positive, I prefer changed_squad unless it has a nice bench
-            if squad.bench().total_metric(1.0) - self.top_squad.bench().total_metric(1.0)
-                > bench_points_required_for_change
-            {
-                self.set_top_squad(&squad);
-                println!("Found a squad was as good but with better value/bench! Squad #: {}, New metric: {:.2}{: <3$}\n", self.n_squads, self.top_adjusted_metric, "", 60);
+                (squad.total_cost() - self.top_cost).millions() * self.config.bench_point_value; // If positive, I prefer changed_squad unless it has a nice bench
+            let sub_points = squad.to_squad(pool).auto_sub_points();
+            if sub_points - self.top_sub_points > bench_points_required_for_change {
+                self.set_top_squad(pool, squad, squad_adjusted_metric);
+                if self.verbose() {
+                    println!("Found a squad was as good but with better value/bench! Squad #: {}, New metric: {:.2}{: <3$}\n", self.n_squads, self.top_adjusted_metric, "", 60);
+                }
             }
         }
-        if self.n_squads % 1000 == 0 {
-            if self.key_poller.poll() {
-                println!("{}", self.changes_for_top());
-            }
+        if self.verbose() && self.n_squads.is_multiple_of(1000) && self.key_poller.poll() {
+            println!("{}", self.changes_for_top());
         }
     }
 }
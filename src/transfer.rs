@@ -0,0 +1,94 @@
+use crate::money::Money;
+use crate::player::Player;
+use crate::squad::Squad;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+// Selling `out` to buy `in_`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Transfer {
+    pub out: Player,
+    #[serde(rename = "in")]
+    pub in_: Player,
+}
+
+impl Transfer {
+    // Money the transfer adds to the bank, negative when the new player is dearer
+    pub fn bank_delta(&self) -> Money {
+        self.out.price - self.in_.price
+    }
+}
+
+pub fn bank_delta(transfers: &[Transfer]) -> Money {
+    transfers.iter().map(Transfer::bank_delta).sum()
+}
+
+// Transfers beyond the free ones, each costing a hit
+pub fn hits(transfers: &[Transfer], free_transfers: usize) -> usize {
+    transfers.len().saturating_sub(free_transfers)
+}
+
+// Transfers written by `optimize --save-plan`, for `transfer --plan` to submit later
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TransferPlan {
+    pub transfers: Vec<Transfer>,
+    pub squad: Squad, // Squad after the transfers
+    #[serde(default)]
+    pub wildcard: bool,
+    #[serde(default)]
+    pub free_hit: bool,
+}
+
+impl TransferPlan {
+    pub fn new(current: &Squad, new: &Squad) -> Self {
+        TransferPlan {
+            transfers: new.transfers_from(current),
+            squad: new.clone(),
+            wildcard: false,
+            free_hit: false,
+        }
+    }
+
+    pub fn players_out(&self) -> Vec<u16> {
+        self.transfers.iter().map(|t| t.out.id).collect()
+    }
+    pub fn players_in(&self) -> Vec<u16> {
+        self.transfers.iter().map(|t| t.in_.id).collect()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+    pub fn load(path: &Path) -> Result<TransferPlan, Box<dyn Error>> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan() {
+        let current: Vec<u16> = (1..=15).collect();
+        let mut new = current.clone();
+        new[3] = 20;
+        let plan = TransferPlan::new(&Squad::with_ids(&current), &Squad::with_ids(&new));
+        assert_eq!(plan.players_out(), vec![4]);
+        assert_eq!(plan.players_in(), vec![20]);
+        assert_eq!(hits(&plan.transfers, 1), 0);
+        assert_eq!(hits(&plan.transfers, 0), 1);
+        assert_eq!(bank_delta(&plan.transfers), Money::ZERO);
+
+        let path = std::env::temp_dir().join(format!("rust_epl_plan_{}", std::process::id()));
+        plan.save(&path).unwrap();
+        let loaded = TransferPlan::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, plan);
+        assert_eq!(loaded.transfers[0].in_.name, "Player20");
+        assert!(loaded.squad.positions_full());
+    }
+}